@@ -4,10 +4,20 @@ pub const MAX_CAP: u64            = 1_000_000_000_000_000;
 pub const MIN_CAP: u64            = 1_000_000_000;
 pub const MAIN_INTEREST: u64      = 150_000_000;
 pub const SECONDARY_INTEREST: u64 = 50_000_000;
+pub const REFERRAL_BUDGET: u64    = 200_000_000;
+pub const MAX_REFERRAL_LEVELS: usize = 5;
+pub const MAX_PARTNER_TIERS: usize = 10;
+pub const MAX_LINKED_WALLETS: usize = 5;
 
 pub const STEP_TAG: &[u8]           = b"STEP";
 pub const PURCHASER_TAG: &[u8]      = b"PURCHASER";
 pub const PARTNER_TAG: &[u8]        = b"PARTNER";
+pub const SIGNER_SET_TAG: &[u8]     = b"SIGNER_SET";
+pub const PROMO_CODE_TAG: &[u8]     = b"PROMO";
+pub const ESCROW_TAG: &[u8]         = b"ESCROW";
+pub const ACCEPTED_TOKEN_TAG: &[u8] = b"ACCEPTED_TOKEN";
+pub const PARTNER_REWARD_TAG: &[u8] = b"PARTNER_REWARD";
+pub const PURCHASE_RECORD_TAG: &[u8] = b"PURCHASE_RECORD";
 pub const BANK: &str                = "5rtu57yuSYYrqRe6VXJUAkZKU9RQpBiReuQ3CFKU2aCN";
 
 pub const SOL_USD_PRICEFEED: &str   = "7UVimffxr9ow1uXYxsr4LHAcV58mLzhmwaeKvJ1pjLiE";
@@ -15,11 +25,15 @@ pub const FEED_ID: &str             = "0xef0d8b6fda2ceba41da15d4095d1da392a0d2f8
 pub const FEED_MAXIMUM_AGE: u64     = 3600; // 1 hour
 
 pub const PRECISION: u32            = 9;
-pub const STABLE_PRECISION: u32     = 3;
 pub const USDT: &str                = "Es9vMFrzaCERmJfrF4H2FYD4KCoNkY11McCe8BenwNYB";
 pub const USDC: &str                = "EPjFWdd5AufqSSqeM2qN1xzybapC8G4wEGGkZwyTDt1v";
 
+pub const PARTNER_CODE_MIN_LEN: usize = 3;
+pub const PARTNER_CODE_MAX_LEN: usize = 32; // max PDA seed length
+pub const RESERVED_PARTNER_CODES: &[&str] = &["admin", "owner", "official", "solanex", "support", "team"];
+
 pub const SIGNATURE_SIGNER: &str     = "DfXfwqnkMMZHjdHJ2Ndhhty15n4okSXyKhrdYUKDNnUe";
+pub const SIGNATURE_VERSION: &str    = "SOLANEX_CLAIM_V1";
 
 const OWNERS: &[&str] = &["2xB43uGrEvZVoUqMYBKBPi6UzWXjavj1BuxjpVErE2gk", "DfXfwqnkMMZHjdHJ2Ndhhty15n4okSXyKhrdYUKDNnUe"];
 
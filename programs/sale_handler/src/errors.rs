@@ -6,6 +6,16 @@ pub enum SaleHandler {
   Unauthorized,
   #[msg("Signature verification failed.")]
   SignatureVerificationFailed,
+  #[msg("Signature verification failed: wrong instruction index")]
+  SignatureVerificationFailedWrongIndex,
+  #[msg("Signature verification failed: not an Ed25519 instruction")]
+  SignatureVerificationFailedWrongProgram,
+  #[msg("Signature verification failed: malformed instruction data")]
+  SignatureVerificationFailedMalformedData,
+  #[msg("Signature verification failed: data outside of the Ed25519 instruction")]
+  SignatureVerificationFailedForeignData,
+  #[msg("Signature verification failed: no matching signature")]
+  SignatureVerificationFailedNoMatch,
   #[msg("SaleHandler already enabled")]
   SaleHandlerEnabled,
   #[msg("SaleHandler already disabled")]
@@ -22,6 +32,18 @@ pub enum SaleHandler {
   SaleHandlerMainPartnerInterestTooLarge,
   #[msg("SaleHandler secondary partner interest too large")]
   SaleHandlerSecondaryPartnerInterestTooLarge,
+  #[msg("SaleHandler TGE percent too large")]
+  SaleHandlerTgePercentTooLarge,
+  #[msg("SaleHandler wrong vesting period")]
+  SaleHandlerWrongVestingPeriod,
+  #[msg("SaleHandler wrong referral interests")]
+  WrongReferralInterests,
+  #[msg("Wrong Partner Tiers Lens")]
+  WrongPartnerTiersLens,
+  #[msg("Wrong Partner Tiers Values")]
+  WrongPartnerTiersValues,
+  #[msg("SaleHandler referral budget too large")]
+  SaleHandlerReferralBudgetTooLarge,
   #[msg("Step supply is too small")]
   StepSupplyTooSmall,
   #[msg("Step already enabled")]
@@ -42,12 +64,114 @@ pub enum SaleHandler {
   WrongBank,
   #[msg("Oracle price is down")]
   PriceIsDown,
+  #[msg("Partner registration closed")]
+  PartnerRegistrationClosed,
+  #[msg("Partner invitation required")]
+  PartnerInvitationRequired,
+  #[msg("Wrong partner code length")]
+  WrongPartnerCodeLength,
+  #[msg("Wrong partner code charset")]
+  WrongPartnerCodeCharset,
+  #[msg("Reserved partner code")]
+  ReservedPartnerCode,
+  #[msg("Partner not registered")]
+  PartnerNotRegistered,
+  #[msg("Partner token account missing")]
+  PartnerTokenAccountMissing,
+  #[msg("Partner commission exceeds payment")]
+  PartnerCommissionTooLarge,
+  #[msg("Referral accounts mismatch")]
+  ReferralAccountsMismatch,
+  #[msg("Referral cycle")]
+  ReferralCycle,
+  #[msg("Partner buyer bonus exceeds referral budget")]
+  PartnerBuyerBonusTooLarge,
+  #[msg("Self referral")]
+  SelfReferral,
+  #[msg("Partner linked wallets too many")]
+  PartnerLinkedWalletsTooMany,
+  #[msg("Partner not enabled")]
+  PartnerNotEnabled,
   #[msg("Partner no funds")]
   PartnerNoFunds,
+  #[msg("Token generation event not reached")]
+  TgeNotReached,
   #[msg("Expired signature")]
   ExpiredSignature,
+  #[msg("Claim amount cap exceeded")]
+  ClaimAmountCapExceeded,
+  #[msg("Signer set is full")]
+  SignerSetFull,
+  #[msg("Signer not found")]
+  SignerNotFound,
+  #[msg("Signer already exists")]
+  SignerAlreadyExists,
+  #[msg("Wrong signer validity window")]
+  WrongSignerValidity,
   #[msg("Wrong Bonuses Lens")]
   WrongBonusesLens,
   #[msg("Wrong Bonuses Values")]
   WrongBonusesValues,
-}
\ No newline at end of file
+  #[msg("Wrong promo code values")]
+  WrongPromoCodeValues,
+  #[msg("Promo code not found")]
+  PromoCodeNotFound,
+  #[msg("Promo code not active")]
+  PromoCodeNotActive,
+  #[msg("Promo code not valid for this step")]
+  PromoCodeWrongStep,
+  #[msg("Promo code redemptions exceeded")]
+  PromoCodeRedemptionsExceeded,
+  #[msg("Promo code wallet redemptions exceeded")]
+  PromoCodeWalletRedemptionsExceeded,
+  #[msg("Wrong holding period")]
+  WrongHoldingPeriod,
+  #[msg("Escrow missing")]
+  EscrowMissing,
+  #[msg("Holding period not passed")]
+  HoldingPeriodNotPassed,
+  #[msg("Wrong claim period")]
+  WrongClaimPeriod,
+  #[msg("Claim period expired")]
+  ClaimPeriodExpired,
+  #[msg("Claim period not expired")]
+  ClaimPeriodNotExpired,
+  #[msg("Withdrawal would leave the account below rent exemption")]
+  RentExemptionViolated,
+  #[msg("Payout accounts mismatch")]
+  PayoutAccountsMismatch,
+  #[msg("Wrong accepted token price")]
+  WrongAcceptedTokenPrice,
+  #[msg("Wrong accepted token cap")]
+  WrongAcceptedTokenCap,
+  #[msg("Accepted token not enabled")]
+  AcceptedTokenNotEnabled,
+  #[msg("Accepted token max cap exceeded")]
+  AcceptedTokenMaxCapExceeded,
+  #[msg("Accepted token min cap not reached")]
+  AcceptedTokenMinCapNotReached,
+  #[msg("Partner reward account missing")]
+  PartnerRewardMissing,
+  #[msg("Wrong depeg guard")]
+  WrongDepegGuard,
+  #[msg("Stablecoin depegged")]
+  StablecoinDepegged,
+  #[msg("Unknown account layout")]
+  UnknownAccountLayout,
+  #[msg("Signer set missing")]
+  SignerSetMissing,
+  #[msg("Partner pending approval")]
+  PartnerPendingApproval,
+  #[msg("Sale handler referral budget below referral interests")]
+  SaleHandlerReferralBudgetTooSmall,
+  #[msg("Purchase record missing")]
+  PurchaseRecordMissing,
+  #[msg("Purchase already clawed back")]
+  PurchaseClawedBack,
+  #[msg("Clawback accounts mismatch")]
+  ClawbackAccountsMismatch,
+  #[msg("Wrong grace period")]
+  WrongGracePeriod,
+  #[msg("Partner missing")]
+  PartnerMissing,
+}
@@ -8,42 +8,156 @@ pub struct PurchaseWithSol {
   pub usd_equivalent: u128,
   pub sol_amount: u64,
   pub token_amount: u128,
+  pub promo_code: String,
+  pub promo_bonus: u128,
 }
 
 #[event]
-pub struct PurchaseWithUsdt {
-  pub step: i16,
+pub struct ReceiveSol {
+  pub partner: String,
+  pub amount: u64,
+}
+
+#[event]
+pub struct ReceiveUsdt {
+  pub partner: String,
+  pub amount: u64,
+}
+
+#[event]
+pub struct ReceiveUsdc {
+  pub partner: String,
+  pub amount: u64,
+}
+
+#[event]
+pub struct ReceiveTokens {
+  pub partner: String,
+  pub amount: u128,
+}
+
+#[event]
+pub struct PartnerOwnershipTransferred {
+  pub partner: String,
+  pub previous_owner: Pubkey,
+  pub new_owner: Pubkey,
+}
+
+#[event]
+pub struct DisabledPartnerPurchase {
+  pub partner: String,
+}
+
+#[event]
+pub struct DisabledPartnerClaim {
+  pub partner: String,
+}
+
+#[event]
+pub struct PartnerRegistered {
+  pub partner: String,
+  pub owner: Pubkey,
+  pub approved: bool,
+}
+
+#[event]
+pub struct ReferralCommission {
+  pub partner: Pubkey,
+  pub level: u8,
+  pub amount: u64,
+}
+
+#[event]
+pub struct PartnerTierChanged {
+  pub partner: String,
+  pub previous_tier: u8,
+  pub tier: u8,
+  pub referred_volume: u128,
+}
+
+#[event]
+pub struct PurchaserPartnerBound {
   pub purchaser: Pubkey,
   pub partner: String,
-  pub usd_equivalent: u128,
-  pub usdt_amount: u64,
+}
+
+#[event]
+pub struct SelfReferralPurchase {
+  pub partner: String,
+  pub purchaser: Pubkey,
+}
+
+#[event]
+pub struct PartnerSettled {
+  pub partner: String,
+  pub sol_amount: u64,
+}
+
+#[event]
+pub struct CommissionClawback {
+  pub partner: Pubkey,
+  pub purchase_record: Pubkey,
+  pub level: u8,
+  pub currency: String,
+  pub amount: u64,
   pub token_amount: u128,
+  // Part already claimed, offset against future earnings
+  pub debt: u64,
 }
 
 #[event]
-pub struct PurchaseWithUsdc {
+pub struct PartnerRewardsSwept {
+  pub partner: String,
+  pub sol_amount: u64,
+  pub usdc_amount: u64,
+  pub usdt_amount: u64,
+}
+
+#[event]
+pub struct PurchaseWithToken {
   pub step: i16,
   pub purchaser: Pubkey,
   pub partner: String,
+  pub mint: Pubkey,
   pub usd_equivalent: u128,
-  pub usdc_amount: u64,
+  pub amount: u64,
+  pub rate: u128,
   pub token_amount: u128,
+  pub promo_code: String,
+  pub promo_bonus: u128,
 }
 
 #[event]
-pub struct ReceiveSol {
+pub struct ReceivePaymentToken {
   pub partner: String,
+  pub mint: Pubkey,
   pub amount: u64,
 }
 
 #[event]
-pub struct ReceiveUsdt {
+pub struct PartnerTokenSettled {
   pub partner: String,
+  pub mint: Pubkey,
   pub amount: u64,
 }
 
 #[event]
-pub struct ReceiveUsdc {
+pub struct AncestorSelfReferralPurchase {
+  pub partner: Pubkey,
+  pub level: u8,
+  pub purchaser: Pubkey,
+}
+
+#[event]
+pub struct PartnerPayoutSkipped {
+  pub partner: String,
+  // `None` when the whole partner is skipped
+  pub currency: Option<String>,
+}
+
+#[event]
+pub struct PartnerTokenRewardSwept {
   pub partner: String,
+  pub mint: Pubkey,
   pub amount: u64,
 }
@@ -0,0 +1,81 @@
+use anchor_lang::prelude::*;
+use anchor_spl::token_interface::Mint;
+use crate::state::accepted_token::AcceptedToken;
+
+use crate::config::ACCEPTED_TOKEN_TAG;
+
+pub fn init_accepted_token(
+  ctx: Context<InitAcceptedToken>,
+  price_update: Pubkey,
+  feed_id: [u8; 32],
+  fixed_price: u64,
+  max_cap: u64,
+  min_cap: u64,
+) -> Result<()> {
+  let accepted_token = &mut ctx.accounts.accepted_token;
+  let mint = &ctx.accounts.mint;
+  accepted_token.init(mint.key())?;
+  accepted_token.set_price_source(price_update, feed_id, fixed_price)?;
+  accepted_token.set_cap(max_cap, min_cap)
+}
+
+pub fn set_accepted_token_price_source(
+  ctx: Context<SetAcceptedToken>,
+  price_update: Pubkey,
+  feed_id: [u8; 32],
+  fixed_price: u64,
+) -> Result<()> {
+  let accepted_token = &mut ctx.accounts.accepted_token;
+  accepted_token.set_price_source(price_update, feed_id, fixed_price)
+}
+
+pub fn set_accepted_token_cap(
+  ctx: Context<SetAcceptedToken>,
+  max_cap: u64,
+  min_cap: u64,
+) -> Result<()> {
+  let accepted_token = &mut ctx.accounts.accepted_token;
+  accepted_token.set_cap(max_cap, min_cap)
+}
+
+pub fn enable_accepted_token(
+  ctx: Context<SetAcceptedToken>,
+) -> Result<()> {
+  let accepted_token = &mut ctx.accounts.accepted_token;
+  accepted_token.set_enable()
+}
+
+pub fn disable_accepted_token(
+  ctx: Context<SetAcceptedToken>,
+) -> Result<()> {
+  let accepted_token = &mut ctx.accounts.accepted_token;
+  accepted_token.set_disable()
+}
+
+#[derive(Accounts)]
+pub struct InitAcceptedToken<'info> {
+  #[account(
+    init,
+    payer = payer,
+    space = 8 + AcceptedToken::MAX_SIZE,
+    seeds = [
+      ACCEPTED_TOKEN_TAG,
+      b"_",
+      mint.key().as_ref()
+    ],
+    bump
+  )]
+  pub accepted_token: Account<'info, AcceptedToken>,
+  pub mint: InterfaceAccount<'info, Mint>,
+  #[account(mut)]
+  pub payer: Signer<'info>,
+  pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct SetAcceptedToken<'info> {
+  #[account(mut)]
+  pub accepted_token: Account<'info, AcceptedToken>,
+  #[account(mut)]
+  pub payer: Signer<'info>,
+}
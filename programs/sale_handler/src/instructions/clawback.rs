@@ -0,0 +1,230 @@
+use anchor_lang::prelude::*;
+use std::str::FromStr;
+use anchor_spl::token_interface::{ self, Mint, TokenAccount, TokenInterface, TransferChecked };
+use anchor_spl::associated_token::get_associated_token_address_with_program_id;
+
+use crate::errors;
+use crate::events;
+use crate::signature::ClaimCurrency;
+use crate::state::partner::Partner;
+use crate::state::escrow::Escrow;
+use crate::state::partner_token_reward::PartnerTokenReward;
+use crate::state::purchase_record::PurchaseRecord;
+use crate::instructions::partner::withdraw_lamports;
+use crate::instructions::sale_handler::get_received_amount;
+
+use crate::config::{ BANK, ESCROW_TAG, PARTNER_TAG, PARTNER_REWARD_TAG, PURCHASE_RECORD_TAG };
+
+// Partner, partner reward and partner PDA ATA of every recorded level
+pub const CLAWBACK_TOKEN_ACCOUNTS_PER_LEVEL: usize = 3;
+
+/// Reverses every level of a refunded SOL purchase, `remaining_accounts` carry the recorded partners in order
+pub fn clawback_sol_purchase<'info>(
+  ctx: Context<'_, '_, 'info, 'info, ClawbackSolPurchase<'info>>,
+) -> Result<()> {
+  let purchase_record = &mut ctx.accounts.purchase_record;
+  let bank_info = &ctx.accounts.bank_info;
+
+  if Pubkey::from_str(BANK) != Ok(bank_info.key()){
+    return Err(error!(errors::SaleHandler::WrongBank))
+  };
+
+  if purchase_record.get_mint() != Pubkey::default() || ctx.remaining_accounts.len() != purchase_record.get_levels_count() {
+    return err!(errors::SaleHandler::ClawbackAccountsMismatch);
+  }
+
+  purchase_record.set_clawed_back()?;
+
+  for ((partner_key, level, amount), partner_info) in purchase_record.get_rewards().zip(ctx.remaining_accounts.iter()) {
+    if partner_info.key() != partner_key {
+      return err!(errors::SaleHandler::ClawbackAccountsMismatch);
+    }
+
+    let mut partner: Account<Partner> = Account::try_from(partner_info)?;
+    let token_amount = if level == 0 { purchase_record.get_token_reward() } else { 0 };
+
+    let (deferred, pending) = partner.clawback_sol_reward(amount).unwrap();
+    partner.clawback_token_reward(token_amount).unwrap();
+    partner.exit(&crate::ID)?;
+
+    if deferred > 0 {
+      let escrow = match ctx.accounts.escrow.as_mut() {
+        Some(escrow) => escrow,
+        None => return err!(errors::SaleHandler::EscrowMissing),
+      };
+
+      withdraw_lamports(&escrow.to_account_info(), bank_info, deferred)?;
+      escrow.release(deferred).unwrap();
+    }
+
+    if pending > 0 {
+      withdraw_lamports(partner_info, bank_info, pending)?;
+    }
+
+    emit!(events::CommissionClawback {
+      partner: partner_key,
+      purchase_record: purchase_record.key(),
+      level: level,
+      currency: ClaimCurrency::Sol.to_string(),
+      amount: amount,
+      token_amount: token_amount,
+      debt: amount - deferred - pending,
+    });
+  }
+
+  Ok(())
+}
+
+/// Reverses every level of a refunded accepted token purchase, `partner_codes` name the recorded partners in order
+pub fn clawback_token_purchase<'info>(
+  ctx: Context<'_, '_, 'info, 'info, ClawbackTokenPurchase<'info>>,
+  partner_codes: Vec<String>,
+) -> Result<()> {
+  let purchase_record = &mut ctx.accounts.purchase_record;
+  let mint = &ctx.accounts.mint;
+  let program = &ctx.accounts.token_program;
+
+  if partner_codes.len() != purchase_record.get_levels_count() || ctx.remaining_accounts.len() != partner_codes.len() * CLAWBACK_TOKEN_ACCOUNTS_PER_LEVEL {
+    return err!(errors::SaleHandler::ClawbackAccountsMismatch);
+  }
+
+  purchase_record.set_clawed_back()?;
+
+  let accounts = ctx.remaining_accounts.chunks(CLAWBACK_TOKEN_ACCOUNTS_PER_LEVEL);
+  for (((partner_key, level, amount), partner_code), accounts) in purchase_record.get_rewards().zip(partner_codes.iter()).zip(accounts) {
+    let (partner_pda, bump) = Pubkey::find_program_address(&[PARTNER_TAG, b"_", partner_code.as_ref()], &crate::ID);
+    let (partner_reward_key, _) = Pubkey::find_program_address(&[PARTNER_REWARD_TAG, b"_", partner_key.as_ref(), mint.key().as_ref()], &crate::ID);
+    let partner_pda_ata = get_associated_token_address_with_program_id(&partner_key, &mint.key(), &program.key());
+    if partner_pda != partner_key || accounts[0].key() != partner_key || accounts[1].key() != partner_reward_key || accounts[2].key() != partner_pda_ata {
+      return err!(errors::SaleHandler::ClawbackAccountsMismatch);
+    }
+
+    let mut partner: Account<Partner> = Account::try_from(&accounts[0])?;
+    let mut partner_reward: Account<PartnerTokenReward> = Account::try_from(&accounts[1])?;
+    let token_amount = if level == 0 { purchase_record.get_token_reward() } else { 0 };
+
+    // Settlement already moved what is no longer deferred out of escrow and its transfer fee never reached the partner
+    let amount = if purchase_record.is_deferred() {
+      let held = amount.min(partner_reward.get_deferred_reward());
+      held + get_received_amount(mint, amount - held)?
+    } else {
+      amount
+    };
+
+    let (deferred, pending) = partner_reward.clawback_reward(amount).unwrap();
+    partner.clawback_token_reward(token_amount).unwrap();
+    partner_reward.exit(&crate::ID)?;
+    partner.exit(&crate::ID)?;
+
+    if deferred > 0 {
+      let (escrow, escrow_ata) = match (&ctx.accounts.escrow, &ctx.accounts.escrow_ata) {
+        (Some(escrow), Some(escrow_ata)) => (escrow, escrow_ata),
+        _ => return err!(errors::SaleHandler::EscrowMissing),
+      };
+
+      let bump = &[ctx.bumps.escrow.unwrap()];
+      let seeds: &[&[u8]] = &[ESCROW_TAG, bump];
+      let signer_seeds = &[seeds];
+
+      let cpi_accounts = TransferChecked {
+        from: escrow_ata.to_account_info(),
+        mint: mint.to_account_info(),
+        to: ctx.accounts.bank_ata.to_account_info(),
+        authority: escrow.to_account_info(),
+      };
+      token_interface::transfer_checked(CpiContext::new_with_signer(program.to_account_info(), cpi_accounts, signer_seeds), deferred, mint.decimals).unwrap();
+    }
+
+    if pending > 0 {
+      let bump = &[bump];
+      let seeds: &[&[u8]] = &[PARTNER_TAG, b"_", partner_code.as_ref(), bump];
+      let signer_seeds = &[seeds];
+
+      let cpi_accounts = TransferChecked {
+        from: accounts[2].to_account_info(),
+        mint: mint.to_account_info(),
+        to: ctx.accounts.bank_ata.to_account_info(),
+        authority: accounts[0].to_account_info(),
+      };
+      token_interface::transfer_checked(CpiContext::new_with_signer(program.to_account_info(), cpi_accounts, signer_seeds), pending, mint.decimals).unwrap();
+    }
+
+    emit!(events::CommissionClawback {
+      partner: partner_key,
+      purchase_record: purchase_record.key(),
+      level: level,
+      currency: ClaimCurrency::Spl(mint.key()).to_string(),
+      amount: amount,
+      token_amount: token_amount,
+      debt: amount - deferred - pending,
+    });
+  }
+
+  Ok(())
+}
+
+#[derive(Accounts)]
+#[instruction(purchaser: Pubkey, index: u64)]
+pub struct ClawbackSolPurchase<'info> {
+  #[account(
+    mut,
+    seeds = [
+      PURCHASE_RECORD_TAG,
+      b"_",
+      purchaser.as_ref(),
+      index.to_le_bytes().as_ref()
+    ],
+    bump
+  )]
+  pub purchase_record: Account<'info, PurchaseRecord>,
+  #[account(
+    mut,
+    seeds = [ESCROW_TAG],
+    bump,
+  )]
+  pub escrow: Option<Account<'info, Escrow>>,
+  #[account(mut)]
+  /// CHECK: bank info
+  pub bank_info: AccountInfo<'info>,
+  #[account(mut)]
+  pub payer: Signer<'info>,
+}
+
+#[derive(Accounts)]
+#[instruction(purchaser: Pubkey, index: u64)]
+pub struct ClawbackTokenPurchase<'info> {
+  #[account(
+    mut,
+    seeds = [
+      PURCHASE_RECORD_TAG,
+      b"_",
+      purchaser.as_ref(),
+      index.to_le_bytes().as_ref()
+    ],
+    bump
+  )]
+  pub purchase_record: Account<'info, PurchaseRecord>,
+  #[account(
+    seeds = [ESCROW_TAG],
+    bump,
+  )]
+  pub escrow: Option<Account<'info, Escrow>>,
+  #[account(address = purchase_record.get_mint())]
+  pub mint: InterfaceAccount<'info, Mint>,
+  #[account(
+    mut,
+    associated_token::mint = mint,
+    associated_token::authority = escrow,
+    associated_token::token_program = token_program,
+  )]
+  pub escrow_ata: Option<InterfaceAccount<'info, TokenAccount>>,
+  #[account(
+    mut,
+    constraint = bank_ata.mint == mint.key(),
+    constraint = bank_ata.owner == BANK.parse::<Pubkey>().unwrap(),
+  )]
+  pub bank_ata: InterfaceAccount<'info, TokenAccount>,
+  pub token_program: Interface<'info, TokenInterface>,
+  #[account(mut)]
+  pub payer: Signer<'info>,
+}
@@ -0,0 +1,196 @@
+use anchor_lang::prelude::*;
+use anchor_spl::token_interface::{ self, Mint, TokenAccount, TokenInterface, TransferChecked };
+use anchor_spl::associated_token::AssociatedToken;
+
+use crate::errors;
+use crate::events;
+use crate::state::sale_handler::SaleHandler;
+use crate::state::partner::Partner;
+use crate::state::escrow::Escrow;
+use crate::state::partner_token_reward::PartnerTokenReward;
+use crate::instructions::partner::withdraw_lamports;
+use crate::instructions::sale_handler::get_received_amount;
+
+use crate::config::{ ESCROW_TAG, PARTNER_TAG, PARTNER_REWARD_TAG };
+
+pub fn init_escrow(
+  ctx: Context<InitEscrow>,
+) -> Result<()> {
+  let escrow = &mut ctx.accounts.escrow;
+  escrow.init()
+}
+
+pub fn settle_partner(
+  ctx: Context<SettlePartner>,
+  partner_code: String,
+) -> Result<()> {
+  let sale_handler = &ctx.accounts.sale_handler;
+  let partner = &mut ctx.accounts.partner;
+  let escrow = &mut ctx.accounts.escrow;
+
+  let clock: Clock = Clock::get()?;
+  let sol_amount = partner.settle_deferred_sol_reward(clock.unix_timestamp, sale_handler.get_holding_period())?;
+
+  withdraw_lamports(&escrow.to_account_info(), &partner.to_account_info(), sol_amount)?;
+  escrow.release(sol_amount).unwrap();
+
+  emit!(events::PartnerSettled {
+    partner: partner_code,
+    sol_amount: sol_amount,
+  });
+
+  Ok(())
+}
+
+pub fn settle_partner_token(
+  ctx: Context<SettlePartnerToken>,
+  partner_code: String,
+) -> Result<()> {
+  let sale_handler = &ctx.accounts.sale_handler;
+  let partner_reward = &mut ctx.accounts.partner_reward;
+  let escrow = &ctx.accounts.escrow;
+  let escrow_ata = &ctx.accounts.escrow_ata;
+  let partner_pda_ata = &ctx.accounts.partner_pda_ata;
+  let mint = &ctx.accounts.mint;
+  let program = &ctx.accounts.token_program;
+
+  let clock: Clock = Clock::get()?;
+  let amount = partner_reward.settle_deferred_reward(clock.unix_timestamp, sale_handler.get_holding_period())?;
+
+  let bump = &[ctx.bumps.escrow];
+  let seeds: &[&[u8]] = &[ESCROW_TAG, bump];
+  let signer_seeds = &[seeds];
+
+  let cpi_accounts = TransferChecked {
+    from: escrow_ata.to_account_info(),
+    mint: mint.to_account_info(),
+    to: partner_pda_ata.to_account_info(),
+    authority: escrow.to_account_info(),
+  };
+  let ctx = CpiContext::new_with_signer(program.to_account_info(), cpi_accounts, signer_seeds);
+  token_interface::transfer_checked(ctx, amount, mint.decimals).unwrap();
+
+  partner_reward.set_reward(get_received_amount(mint, amount)?).unwrap();
+
+  emit!(events::PartnerTokenSettled {
+    partner: partner_code,
+    mint: escrow_ata.mint,
+    amount: amount,
+  });
+
+  Ok(())
+}
+
+/// Escrow receiving commissions in deferred settlement, `None` when they are paid out at purchase
+pub fn get_escrow<'a, 'info>(
+  sale_handler: &SaleHandler,
+  escrow: &'a mut Option<Account<'info, Escrow>>,
+) -> Result<Option<&'a mut Account<'info, Escrow>>> {
+  if !sale_handler.is_deferred_settlement() {
+    return Ok(None);
+  }
+
+  match escrow.as_mut() {
+    Some(escrow) => Ok(Some(escrow)),
+    None => err!(errors::SaleHandler::EscrowMissing),
+  }
+}
+
+#[derive(Accounts)]
+pub struct InitEscrow<'info> {
+  #[account(
+    init,
+    payer = payer,
+    space = 8 + Escrow::MAX_SIZE,
+    seeds = [ESCROW_TAG],
+    bump
+  )]
+  pub escrow: Account<'info, Escrow>,
+  #[account(mut)]
+  pub payer: Signer<'info>,
+  pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(partner_code: String)]
+pub struct SettlePartner<'info> {
+  #[account(
+    mut,
+    seeds = [
+      PARTNER_TAG,
+      b"_",
+      partner_code.as_ref()
+    ],
+    bump
+  )]
+  pub partner: Account<'info, Partner>,
+  #[account(
+    seeds = [],
+    bump,
+  )]
+  pub sale_handler: Account<'info, SaleHandler>,
+  #[account(
+    mut,
+    seeds = [ESCROW_TAG],
+    bump,
+  )]
+  pub escrow: Account<'info, Escrow>,
+  #[account(mut)]
+  pub payer: Signer<'info>,
+}
+
+#[derive(Accounts)]
+#[instruction(partner_code: String)]
+pub struct SettlePartnerToken<'info> {
+  #[account(
+    seeds = [
+      PARTNER_TAG,
+      b"_",
+      partner_code.as_ref()
+    ],
+    bump
+  )]
+  pub partner: Account<'info, Partner>,
+  #[account(
+    mut,
+    seeds = [
+      PARTNER_REWARD_TAG,
+      b"_",
+      partner.key().as_ref(),
+      mint.key().as_ref()
+    ],
+    bump
+  )]
+  pub partner_reward: Account<'info, PartnerTokenReward>,
+  #[account(
+    seeds = [],
+    bump,
+  )]
+  pub sale_handler: Account<'info, SaleHandler>,
+  #[account(
+    seeds = [ESCROW_TAG],
+    bump,
+  )]
+  pub escrow: Account<'info, Escrow>,
+  pub mint: InterfaceAccount<'info, Mint>,
+  #[account(
+    mut,
+    associated_token::mint = mint,
+    associated_token::authority = escrow,
+    associated_token::token_program = token_program,
+  )]
+  pub escrow_ata: InterfaceAccount<'info, TokenAccount>,
+  #[account(
+    init_if_needed,
+    payer = payer,
+    associated_token::mint = mint,
+    associated_token::authority = partner,
+    associated_token::token_program = token_program,
+  )]
+  pub partner_pda_ata: InterfaceAccount<'info, TokenAccount>,
+  pub token_program: Interface<'info, TokenInterface>,
+  pub associated_token_program: Program<'info, AssociatedToken>,
+  pub system_program: Program<'info, System>,
+  #[account(mut)]
+  pub payer: Signer<'info>,
+}
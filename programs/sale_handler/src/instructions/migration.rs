@@ -0,0 +1,161 @@
+use anchor_lang::{
+  prelude::*,
+  Discriminator,
+  solana_program::{ program::invoke, system_instruction::transfer },
+};
+
+use crate::errors;
+use crate::state::sale_handler::{ SaleHandler, LegacySaleHandler };
+use crate::state::partner::{ Partner, LegacyPartner };
+use crate::state::purchaser::Purchaser;
+
+use crate::config::{ PARTNER_TAG, PURCHASER_TAG };
+
+/// Rewrites the sale handler of the first deployment into the current layout, keeping its caps, interests and bonuses
+pub fn migrate_sale_handler(
+  ctx: Context<MigrateSaleHandler>,
+) -> Result<()> {
+  let sale_handler_info = &ctx.accounts.sale_handler;
+  if sale_handler_info.data_len() == 8 + SaleHandler::MAX_SIZE {
+    return Ok(());
+  }
+
+  let legacy = {
+    let data = sale_handler_info.try_borrow_data()?;
+    if data.len() != 8 + LegacySaleHandler::MAX_SIZE || data[..8] != SaleHandler::DISCRIMINATOR {
+      return err!(errors::SaleHandler::UnknownAccountLayout);
+    }
+
+    LegacySaleHandler::deserialize(&mut &data[8..])?
+  };
+
+  resize_account(sale_handler_info, &ctx.accounts.payer, &ctx.accounts.system_program, 8 + SaleHandler::MAX_SIZE)?;
+
+  let mut data = sale_handler_info.try_borrow_mut_data()?;
+  let mut sale_handler = SaleHandler::deserialize(&mut &vec![0u8; SaleHandler::MAX_SIZE][..])?;
+  sale_handler.migrate(&legacy).unwrap();
+  data.fill(0);
+  sale_handler.try_serialize(&mut &mut data[..])?;
+
+  Ok(())
+}
+
+/// Rewrites a partner of the first deployment into the current layout, keeping its interests and pending rewards
+pub fn migrate_partner(
+  ctx: Context<MigratePartner>,
+  owner: Pubkey,
+  registered: bool,
+) -> Result<()> {
+  let partner_info = &ctx.accounts.partner;
+  if partner_info.data_len() == 8 + Partner::MAX_SIZE {
+    return Ok(());
+  }
+
+  let legacy = {
+    let data = partner_info.try_borrow_data()?;
+    if data.len() != 8 + LegacyPartner::MAX_SIZE || data[..8] != Partner::DISCRIMINATOR {
+      return err!(errors::SaleHandler::UnknownAccountLayout);
+    }
+
+    LegacyPartner::deserialize(&mut &data[8..])?
+  };
+
+  resize_account(partner_info, &ctx.accounts.payer, &ctx.accounts.system_program, 8 + Partner::MAX_SIZE)?;
+
+  let mut data = partner_info.try_borrow_mut_data()?;
+  let mut partner = Partner::deserialize(&mut &vec![0u8; Partner::MAX_SIZE][..])?;
+  partner.migrate(&legacy, owner, registered).unwrap();
+  data.fill(0);
+  partner.try_serialize(&mut &mut data[..])?;
+
+  Ok(())
+}
+
+/// Grows the payer purchaser account, appended fields start zeroed
+pub fn migrate_purchaser(
+  ctx: Context<MigratePurchaser>,
+) -> Result<()> {
+  let purchaser_info = &ctx.accounts.purchaser;
+  if purchaser_info.data_len() >= 8 + Purchaser::MAX_SIZE {
+    return Ok(());
+  }
+
+  if purchaser_info.try_borrow_data()?[..8] != Purchaser::DISCRIMINATOR {
+    return err!(errors::SaleHandler::UnknownAccountLayout);
+  }
+
+  resize_account(purchaser_info, &ctx.accounts.payer, &ctx.accounts.system_program, 8 + Purchaser::MAX_SIZE)
+}
+
+/// Grows a program account to `space` bytes, the payer covering the additional rent
+fn resize_account<'info>(
+  account: &AccountInfo<'info>,
+  payer: &Signer<'info>,
+  system_program: &Program<'info, System>,
+  space: usize,
+) -> Result<()> {
+  let rent = Rent::get()?;
+  let lamports = rent.minimum_balance(space).saturating_sub(rent.minimum_balance(account.data_len()));
+  if lamports > 0 {
+    let instruction = &transfer(&payer.key(), &account.key(), lamports);
+    invoke(instruction, &[payer.to_account_info(), account.clone(), system_program.to_account_info()])?;
+  }
+
+  account.realloc(space, true)?;
+
+  Ok(())
+}
+
+#[derive(Accounts)]
+pub struct MigrateSaleHandler<'info> {
+  #[account(
+    mut,
+    owner = crate::ID,
+    seeds = [],
+    bump,
+  )]
+  /// CHECK: deserialized by hand, the stored layout predates the current one
+  pub sale_handler: AccountInfo<'info>,
+  #[account(mut)]
+  pub payer: Signer<'info>,
+  pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(partner_code: String)]
+pub struct MigratePartner<'info> {
+  #[account(
+    mut,
+    owner = crate::ID,
+    seeds = [
+      PARTNER_TAG,
+      b"_",
+      partner_code.as_ref()
+    ],
+    bump
+  )]
+  /// CHECK: deserialized by hand, the stored layout predates the current one
+  pub partner: AccountInfo<'info>,
+  #[account(mut)]
+  pub payer: Signer<'info>,
+  pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct MigratePurchaser<'info> {
+  #[account(
+    mut,
+    owner = crate::ID,
+    seeds = [
+      PURCHASER_TAG,
+      b"_",
+      payer.key().as_ref()
+    ],
+    bump
+  )]
+  /// CHECK: deserialized by hand, the stored layout predates the current one
+  pub purchaser: AccountInfo<'info>,
+  #[account(mut)]
+  pub payer: Signer<'info>,
+  pub system_program: Program<'info, System>,
+}
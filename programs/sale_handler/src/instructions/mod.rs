@@ -1,6 +1,18 @@
 pub use sale_handler::*;
 pub use step::*;
 pub use partner::*;
+pub use signer_set::*;
+pub use promo_code::*;
+pub use escrow::*;
+pub use clawback::*;
+pub use accepted_token::*;
+pub use migration::*;
 pub mod sale_handler;
 pub mod step;
-pub mod partner;
\ No newline at end of file
+pub mod partner;
+pub mod signer_set;
+pub mod promo_code;
+pub mod escrow;
+pub mod clawback;
+pub mod accepted_token;
+pub mod migration;
\ No newline at end of file
@@ -1,19 +1,99 @@
 use anchor_lang::prelude::*;
-use anchor_spl::token::{self, Token, TokenAccount, Transfer as SplTransfer};
+use anchor_spl::token_interface::{self, Mint, TokenAccount, TokenInterface, TransferChecked};
+use anchor_spl::associated_token::get_associated_token_address_with_program_id;
 use solana_program::sysvar::instructions::ID as IX_ID;
-use crate::config::{ USDC, USDT, PARTNER_TAG };
+use std::str::FromStr;
+use crate::config::{
+  BANK, USDC, USDT, PARTNER_TAG, SIGNER_SET_TAG, PURCHASER_TAG, PARTNER_REWARD_TAG,
+  PARTNER_CODE_MIN_LEN, PARTNER_CODE_MAX_LEN, RESERVED_PARTNER_CODES,
+};
 
 use crate::events;
 use crate::errors;
 use crate::state::partner::*;
+use crate::state::sale_handler::{ SaleHandler, DisabledPartnerClaimPolicy, PartnerRegistration };
+use crate::state::signer_set::SignerSet;
+use crate::state::purchaser::Purchaser;
+use crate::state::partner_token_reward::PartnerTokenReward;
+use crate::signature::ClaimCurrency;
+
+// Partner, owner wallet, partner and owner USDC ATAs, partner and owner USDT ATAs
+pub const PAYOUT_ACCOUNTS_PER_PARTNER: usize = 6;
+// Partner, owner wallet, partner reward, partner and owner ATAs of the paid mint
+pub const PAYOUT_TOKEN_ACCOUNTS_PER_PARTNER: usize = 5;
 
 pub fn init_partner(
   ctx: Context<InitPartner>,
+  owner: Pubkey,
   main_interest: u64,
   secondary_interest: u64,
 ) -> Result<()> {
   let partner = &mut ctx.accounts.partner;
-  partner.init(main_interest, secondary_interest)
+  partner.init(owner, main_interest, secondary_interest)
+}
+
+pub fn register_partner(
+  ctx: Context<RegisterPartner>,
+  partner_code: String,
+  invited: bool,
+) -> Result<()> {
+  let payer = &ctx.accounts.payer;
+  let sale_handler = &mut ctx.accounts.sale_handler;
+  let partner = &mut ctx.accounts.partner;
+
+  validate_partner_code(&partner_code)?;
+
+  let registration = sale_handler.get_partner_registration();
+  if registration == PartnerRegistration::Closed {
+    return err!(errors::SaleHandler::PartnerRegistrationClosed);
+  }
+
+  if registration == PartnerRegistration::Invitation && !invited {
+    return err!(errors::SaleHandler::PartnerInvitationRequired);
+  }
+
+  // No interest override, the partner follows the sale-wide default and tiers
+  partner.init(payer.key(), 0, 0).unwrap();
+
+  // Waits for `enable_partner` from an admin
+  let approved = registration != PartnerRegistration::Approval;
+  if !approved {
+    partner.set_pending().unwrap();
+  }
+
+  emit!(events::PartnerRegistered {
+    partner: partner_code,
+    owner: payer.key(),
+    approved: approved,
+  });
+
+  Ok(())
+}
+
+pub fn transfer_partner_ownership(
+  ctx: Context<TransferPartnerOwnership>,
+  partner_code: String,
+  new_owner: Pubkey,
+) -> Result<()> {
+  let partner = &mut ctx.accounts.partner;
+  let previous_owner = partner.get_owner();
+  partner.set_owner(new_owner).unwrap();
+
+  emit!(events::PartnerOwnershipTransferred {
+    partner: partner_code,
+    previous_owner: previous_owner,
+    new_owner: new_owner,
+  });
+
+  Ok(())
+}
+
+pub fn set_partner_signed_claims(
+  ctx: Context<SetPartnerSignedClaims>,
+  signed_claims: bool,
+) -> Result<()> {
+  let partner = &mut ctx.accounts.partner;
+  partner.set_signed_claims(signed_claims)
 }
 
 pub fn set_partner_interest(
@@ -25,6 +105,60 @@ pub fn set_partner_interest(
   partner.set_interest(main_interest, secondary_interest)
 }
 
+pub fn set_partner_parent(
+  ctx: Context<SetPartnerParent>,
+) -> Result<()> {
+  let partner = &mut ctx.accounts.partner;
+  let parent = match &ctx.accounts.parent {
+    Some(parent) if !parent.is_registered() => return err!(errors::SaleHandler::PartnerNotRegistered),
+    Some(parent) => parent.key(),
+    None => Pubkey::default(),
+  };
+
+  partner.set_parent(parent)
+}
+
+pub fn set_partner_buyer_bonus(
+  ctx: Context<SetPartnerBuyerBonus>,
+  buyer_bonus: u64,
+) -> Result<()> {
+  let partner = &mut ctx.accounts.partner;
+  let sale_handler = &ctx.accounts.sale_handler;
+
+  if buyer_bonus > sale_handler.get_referral_budget() {
+    return err!(errors::SaleHandler::PartnerBuyerBonusTooLarge);
+  }
+
+  partner.set_buyer_bonus(buyer_bonus)
+}
+
+pub fn set_purchaser_partner(
+  ctx: Context<SetPurchaserPartner>,
+  _wallet: Pubkey,
+  partner_code: String,
+) -> Result<()> {
+  let purchaser = &mut ctx.accounts.purchaser;
+
+  if !partner_code.is_empty() {
+    match &ctx.accounts.partner {
+      Some(partner) if partner.is_pending() => return err!(errors::SaleHandler::PartnerPendingApproval),
+      Some(partner) if partner.is_registered() => (),
+      _ => return err!(errors::SaleHandler::PartnerNotRegistered),
+    }
+  }
+
+  purchaser.set_partner_code(partner_code)
+}
+
+pub fn set_partner_limits(
+  ctx: Context<SetPartnerLimits>,
+  linked_wallets: Vec<Pubkey>,
+  commission_cap_usd: u128,
+) -> Result<()> {
+  let partner = &mut ctx.accounts.partner;
+  partner.set_limits(linked_wallets, commission_cap_usd)
+}
+
 pub fn enable_partner(
   ctx: Context<SetPartnerEnabled>,
 ) -> Result<()> {
@@ -42,16 +176,24 @@ pub fn disable_partner(
 pub fn receive_sol(
   ctx: Context<ReceiveSol>,
   partner_code: String,
+  amount_cap: u128,
 ) -> Result<()> {
   let payer = &mut ctx.accounts.payer;
   let partner = &mut ctx.accounts.partner;
+  let sale_handler = &ctx.accounts.sale_handler;
+
+  check_partner_claim(sale_handler, partner, &partner_code)?;
+  check_claim_period(sale_handler)?;
   
   let sol_interest = partner.get_sol_reward();
+  if u128::from(sol_interest) > amount_cap {
+    return err!(errors::SaleHandler::ClaimAmountCapExceeded);
+  }
+
   if sol_interest > 0 {
     partner.reset_sol_reward().unwrap();
 
-    partner.sub_lamports(sol_interest).unwrap();
-    payer.add_lamports(sol_interest).unwrap();
+    withdraw_lamports(&partner.to_account_info(), &payer.to_account_info(), sol_interest)?;
 
     emit!(events::ReceiveSol {
       partner: partner_code,
@@ -65,32 +207,43 @@ pub fn receive_sol(
 pub fn receive_usdc(
   ctx: Context<ReceiveUSDC>,
   partner_code: String,
+  amount_cap: u128,
 ) -> Result<()> {
   //let payer = &mut ctx.accounts.payer;
   let partner = &mut ctx.accounts.partner;
+  let sale_handler = &ctx.accounts.sale_handler;
   
   let partner_ata = &ctx.accounts.partner_ata;
   let partner_pda_ata = &ctx.accounts.partner_pda_ata;
+  let mint = &ctx.accounts.mint;
   let program = &ctx.accounts.token_program;
 
+  check_partner_claim(sale_handler, partner, &partner_code)?;
+  check_claim_period(sale_handler)?;
+
   let amount = partner.get_usdc_reward();
   if amount == 0 {
     return err!(errors::SaleHandler::PartnerNoFunds);
   }
 
+  if u128::from(amount) > amount_cap {
+    return err!(errors::SaleHandler::ClaimAmountCapExceeded);
+  }
+
   partner.reset_usdc_reward().unwrap();
 
   let bump = &[ctx.bumps.partner];
   let seeds: &[&[u8]] = &[PARTNER_TAG, b"_", partner_code.as_ref(), bump];
   let signer_seeds = &[&seeds[..]];
 
-  let cpi_accounts = SplTransfer {
+  let cpi_accounts = TransferChecked {
     from: partner_pda_ata.to_account_info(),
+    mint: mint.to_account_info(),
     to: partner_ata.to_account_info(),
     authority: partner.to_account_info(),
   };
   let ctx = CpiContext::new_with_signer(program.to_account_info(), cpi_accounts, signer_seeds);
-  token::transfer(ctx, amount).unwrap();
+  token_interface::transfer_checked(ctx, amount, mint.decimals).unwrap();
 
   emit!(events::ReceiveUsdc {
     partner: partner_code,
@@ -103,32 +256,43 @@ pub fn receive_usdc(
 pub fn receive_usdt(
   ctx: Context<ReceiveUSDT>,
   partner_code: String,
+  amount_cap: u128,
 ) -> Result<()> {
   //let payer = &mut ctx.accounts.payer;
   let partner = &mut ctx.accounts.partner;
+  let sale_handler = &ctx.accounts.sale_handler;
   
   let partner_ata = &ctx.accounts.partner_ata;
   let partner_pda_ata = &ctx.accounts.partner_pda_ata;
+  let mint = &ctx.accounts.mint;
   let program = &ctx.accounts.token_program;
 
+  check_partner_claim(sale_handler, partner, &partner_code)?;
+  check_claim_period(sale_handler)?;
+
   let amount = partner.get_usdt_reward();
   if amount == 0 {
     return err!(errors::SaleHandler::PartnerNoFunds);
   }
 
+  if u128::from(amount) > amount_cap {
+    return err!(errors::SaleHandler::ClaimAmountCapExceeded);
+  }
+
   partner.reset_usdt_reward().unwrap();
 
   let bump = &[ctx.bumps.partner];
   let seeds: &[&[u8]] = &[PARTNER_TAG, b"_", partner_code.as_ref(), bump];
   let signer_seeds = &[&seeds[..]];
 
-  let cpi_accounts = SplTransfer {
+  let cpi_accounts = TransferChecked {
     from: partner_pda_ata.to_account_info(),
+    mint: mint.to_account_info(),
     to: partner_ata.to_account_info(),
     authority: partner.to_account_info(),
   };
   let ctx = CpiContext::new_with_signer(program.to_account_info(), cpi_accounts, signer_seeds);
-  token::transfer(ctx, amount).unwrap();
+  token_interface::transfer_checked(ctx, amount, mint.decimals).unwrap();
 
   emit!(events::ReceiveUsdt {
     partner: partner_code,
@@ -138,138 +302,1092 @@ pub fn receive_usdt(
   Ok(())
 }
 
-#[derive(Accounts)]
-#[instruction(partner_code: String)]
-pub struct InitPartner<'info> {
-  #[account(
-    init,
-    payer = payer,
-    space = 8 + Partner::MAX_SIZE,
-    seeds = [
-      PARTNER_TAG,
-      b"_",
-      partner_code.as_ref()
-    ],
-    bump
-  )]
-  pub partner: Account<'info, Partner>,
-  #[account(mut)]
-  pub payer: Signer<'info>,
-  pub system_program: Program<'info, System>,
-}
+pub fn receive_payment_token(
+  ctx: Context<ReceivePaymentToken>,
+  partner_code: String,
+  amount_cap: u128,
+) -> Result<()> {
+  let partner = &mut ctx.accounts.partner;
+  let partner_reward = &mut ctx.accounts.partner_reward;
+  let sale_handler = &ctx.accounts.sale_handler;
 
-#[derive(Accounts)]
-pub struct SetPartnerInterest<'info> {
-  #[account(mut)]
-  pub partner: Account<'info, Partner>,
-  #[account(mut)]
-  pub payer: Signer<'info>,
+  let partner_ata = &ctx.accounts.partner_ata;
+  let partner_pda_ata = &ctx.accounts.partner_pda_ata;
+  let mint = &ctx.accounts.mint;
+  let program = &ctx.accounts.token_program;
+
+  check_partner_claim(sale_handler, partner, &partner_code)?;
+  check_claim_period(sale_handler)?;
+
+  let amount = partner_reward.get_reward();
+  if amount == 0 {
+    return err!(errors::SaleHandler::PartnerNoFunds);
+  }
+
+  if u128::from(amount) > amount_cap {
+    return err!(errors::SaleHandler::ClaimAmountCapExceeded);
+  }
+
+  partner_reward.reset_reward().unwrap();
+
+  let bump = &[ctx.bumps.partner];
+  let seeds: &[&[u8]] = &[PARTNER_TAG, b"_", partner_code.as_ref(), bump];
+  let signer_seeds = &[seeds];
+
+  let cpi_accounts = TransferChecked {
+    from: partner_pda_ata.to_account_info(),
+    mint: mint.to_account_info(),
+    to: partner_ata.to_account_info(),
+    authority: partner.to_account_info(),
+  };
+  let ctx = CpiContext::new_with_signer(program.to_account_info(), cpi_accounts, signer_seeds);
+  token_interface::transfer_checked(ctx, amount, mint.decimals).unwrap();
+
+  emit!(events::ReceivePaymentToken {
+    partner: partner_code,
+    mint: partner_pda_ata.mint,
+    amount: amount,
+  });
+
+  Ok(())
 }
 
-#[derive(Accounts)]
-pub struct SetPartnerEnabled<'info> {
-  #[account(mut)]
-  pub partner: Account<'info, Partner>,
-  #[account(mut)]
-  pub payer: Signer<'info>,
+pub fn receive_tokens(
+  ctx: Context<ReceiveTokens>,
+  partner_code: String,
+  amount_cap: u128,
+) -> Result<()> {
+  let partner = &mut ctx.accounts.partner;
+  let sale_handler = &ctx.accounts.sale_handler;
+
+  let partner_token_ata = &ctx.accounts.partner_token_ata;
+  let token_vault = &ctx.accounts.token_vault;
+  let mint = &ctx.accounts.mint;
+  let program = &ctx.accounts.token_program;
+
+  check_partner_claim(sale_handler, partner, &partner_code)?;
+
+  let clock: Clock = Clock::get()?;
+  if !sale_handler.is_tge_reached(clock.unix_timestamp) {
+    return err!(errors::SaleHandler::TgeNotReached);
+  }
+
+  let unlocked = sale_handler.calculate_unlocked(partner.get_token_reward(), clock.unix_timestamp);
+  let amount = unlocked.saturating_sub(partner.get_token_reward_claimed());
+  if amount == 0 {
+    return err!(errors::SaleHandler::PartnerNoFunds);
+  }
+
+  if amount > amount_cap {
+    return err!(errors::SaleHandler::ClaimAmountCapExceeded);
+  }
+
+  partner.set_token_reward_claimed(amount).unwrap();
+
+  let bump = &[ctx.bumps.sale_handler];
+  let seeds: &[&[u8]] = &[bump];
+  let signer_seeds = &[seeds];
+
+  let cpi_accounts = TransferChecked {
+    from: token_vault.to_account_info(),
+    mint: mint.to_account_info(),
+    to: partner_token_ata.to_account_info(),
+    authority: sale_handler.to_account_info(),
+  };
+  let ctx = CpiContext::new_with_signer(program.to_account_info(), cpi_accounts, signer_seeds);
+  token_interface::transfer_checked(ctx, u64::try_from(amount).unwrap(), mint.decimals).unwrap();
+
+  emit!(events::ReceiveTokens {
+    partner: partner_code,
+    amount: amount,
+  });
+
+  Ok(())
 }
 
-#[derive(Accounts)]
-pub struct SetPartnerDisabled<'info> {
-  #[account(mut)]
-  pub partner: Account<'info, Partner>,
-  #[account(mut)]
-  pub payer: Signer<'info>,
+/// SOL and stablecoin rewards are claimable until the claim period after finalization ends
+fn check_claim_period(
+  sale_handler: &SaleHandler,
+) -> Result<()> {
+  let clock: Clock = Clock::get()?;
+  if sale_handler.is_claim_expired(clock.unix_timestamp) {
+    return err!(errors::SaleHandler::ClaimPeriodExpired);
+  }
+
+  Ok(())
 }
 
-#[derive(Accounts)]
-#[instruction(partner_code: String)]
-pub struct ReceiveSol<'info> {
-  #[account(
-    mut,
-    seeds = [
-      PARTNER_TAG,
-      b"_",
-      partner_code.as_ref()
-    ],
-    bump
-  )]
-  pub partner: Account<'info, Partner>,
+/// Moves lamports out of a program account, never leaving it below rent exemption
+pub fn withdraw_lamports<'info>(
+  from: &AccountInfo<'info>,
+  to: &AccountInfo<'info>,
+  amount: u64,
+) -> Result<()> {
+  let rent = Rent::get()?;
+  let available = from.lamports().saturating_sub(rent.minimum_balance(from.data_len()));
+  if amount > available {
+    return err!(errors::SaleHandler::RentExemptionViolated);
+  }
 
-  #[account(address = IX_ID)]
-  /// CHECK: we need this for sign
-  pub ix_sysvar: AccountInfo<'info>,
+  from.sub_lamports(amount)?;
+  to.add_lamports(amount)?;
 
-  #[account(mut)]
-  pub payer: Signer<'info>,
+  Ok(())
 }
 
-#[derive(Accounts)]
-#[instruction(partner_code: String)]
-pub struct ReceiveUSDC<'info> {
-  #[account(
-    mut,
-    seeds = [
-      PARTNER_TAG,
-      b"_",
-      partner_code.as_ref()
-    ],
-    bump
-  )]
-  pub partner: Account<'info, Partner>,
-  #[account(
-    mut,
-    constraint = partner_ata.mint == USDC.parse::<Pubkey>().unwrap(),
-    constraint = partner_ata.owner == payer.key(),
-  )]
-  pub partner_ata: Account<'info, TokenAccount>,
-  #[account(
-    mut,
-    constraint = partner_pda_ata.mint == USDC.parse::<Pubkey>().unwrap(),
-    constraint = partner_pda_ata.owner == partner.key(),
-  )]
-  pub partner_pda_ata: Account<'info, TokenAccount>,
-  pub token_program: Program<'info, Token>,
+/// Moves rewards left unclaimed past the claim period from the partner PDA to the bank
+pub fn sweep_partner_rewards(
+  ctx: Context<SweepPartnerRewards>,
+  partner_code: String,
+) -> Result<()> {
+  let partner = &mut ctx.accounts.partner;
+  let sale_handler = &ctx.accounts.sale_handler;
+  let bank_info = &ctx.accounts.bank_info;
+  let program = &ctx.accounts.token_program;
 
-  #[account(address = IX_ID)]
-  /// CHECK: we need this for sign
-  pub ix_sysvar: AccountInfo<'info>,
+  if Pubkey::from_str(BANK) != Ok(bank_info.key()){
+    return Err(error!(errors::SaleHandler::WrongBank))
+  };
 
-  #[account(mut)]
-  pub payer: Signer<'info>,
-}
+  let clock: Clock = Clock::get()?;
+  if !sale_handler.is_claim_expired(clock.unix_timestamp) {
+    return err!(errors::SaleHandler::ClaimPeriodNotExpired);
+  }
 
-#[derive(Accounts)]
-#[instruction(partner_code: String)]
-pub struct ReceiveUSDT<'info> {
-  #[account(
-    mut,
-    seeds = [
-      PARTNER_TAG,
-      b"_",
-      partner_code.as_ref()
-    ],
-    bump
-  )]
-  pub partner: Account<'info, Partner>,
-  #[account(
-    mut,
-    constraint = partner_ata.mint == USDT.parse::<Pubkey>().unwrap(),
-    constraint = partner_ata.owner == payer.key(),
-  )]
-  pub partner_ata: Account<'info, TokenAccount>,
-  #[account(
-    mut,
-    constraint = partner_pda_ata.mint == USDT.parse::<Pubkey>().unwrap(),
-    constraint = partner_pda_ata.owner == partner.key(),
-  )]
-  pub partner_pda_ata: Account<'info, TokenAccount>,
-  pub token_program: Program<'info, Token>,
+  let sol_amount = partner.get_sol_reward();
+  let usdc_amount = partner.get_usdc_reward();
+  let usdt_amount = partner.get_usdt_reward();
 
-  #[account(address = IX_ID)]
-  /// CHECK: we need this for sign
-  pub ix_sysvar: AccountInfo<'info>,
+  if sol_amount > 0 {
+    partner.sweep_sol_reward().unwrap();
 
-  #[account(mut)]
-  pub payer: Signer<'info>,
+    withdraw_lamports(&partner.to_account_info(), bank_info, sol_amount)?;
+  }
+
+  let bump = &[ctx.bumps.partner];
+  let seeds: &[&[u8]] = &[PARTNER_TAG, b"_", partner_code.as_ref(), bump];
+  let signer_seeds = &[seeds];
+
+  if usdc_amount > 0 {
+    let (mint, partner_pda_ata, bank_ata) = match (&ctx.accounts.usdc_mint, &ctx.accounts.partner_pda_usdc_ata, &ctx.accounts.bank_usdc_ata) {
+      (Some(mint), Some(partner_pda_ata), Some(bank_ata)) => (mint, partner_pda_ata, bank_ata),
+      _ => return err!(errors::SaleHandler::PartnerTokenAccountMissing),
+    };
+
+    partner.sweep_usdc_reward().unwrap();
+
+    let cpi_accounts = TransferChecked {
+      from: partner_pda_ata.to_account_info(),
+      mint: mint.to_account_info(),
+      to: bank_ata.to_account_info(),
+      authority: partner.to_account_info(),
+    };
+    token_interface::transfer_checked(CpiContext::new_with_signer(program.to_account_info(), cpi_accounts, signer_seeds), usdc_amount, mint.decimals).unwrap();
+  }
+
+  if usdt_amount > 0 {
+    let (mint, partner_pda_ata, bank_ata) = match (&ctx.accounts.usdt_mint, &ctx.accounts.partner_pda_usdt_ata, &ctx.accounts.bank_usdt_ata) {
+      (Some(mint), Some(partner_pda_ata), Some(bank_ata)) => (mint, partner_pda_ata, bank_ata),
+      _ => return err!(errors::SaleHandler::PartnerTokenAccountMissing),
+    };
+
+    partner.sweep_usdt_reward().unwrap();
+
+    let cpi_accounts = TransferChecked {
+      from: partner_pda_ata.to_account_info(),
+      mint: mint.to_account_info(),
+      to: bank_ata.to_account_info(),
+      authority: partner.to_account_info(),
+    };
+    token_interface::transfer_checked(CpiContext::new_with_signer(program.to_account_info(), cpi_accounts, signer_seeds), usdt_amount, mint.decimals).unwrap();
+  }
+
+  emit!(events::PartnerRewardsSwept {
+    partner: partner_code,
+    sol_amount: sol_amount,
+    usdc_amount: usdc_amount,
+    usdt_amount: usdt_amount,
+  });
+
+  Ok(())
+}
+
+/// Moves the accepted token rewards left unclaimed past the claim period from the partner PDA to the bank
+pub fn sweep_partner_token_reward(
+  ctx: Context<SweepPartnerTokenReward>,
+  partner_code: String,
+) -> Result<()> {
+  let partner = &ctx.accounts.partner;
+  let partner_reward = &mut ctx.accounts.partner_reward;
+  let sale_handler = &ctx.accounts.sale_handler;
+  let mint = &ctx.accounts.mint;
+  let program = &ctx.accounts.token_program;
+
+  let clock: Clock = Clock::get()?;
+  if !sale_handler.is_claim_expired(clock.unix_timestamp) {
+    return err!(errors::SaleHandler::ClaimPeriodNotExpired);
+  }
+
+  let amount = partner_reward.get_reward();
+  if amount > 0 {
+    partner_reward.sweep_reward().unwrap();
+
+    let bump = &[ctx.bumps.partner];
+    let seeds: &[&[u8]] = &[PARTNER_TAG, b"_", partner_code.as_ref(), bump];
+    let signer_seeds = &[seeds];
+
+    let cpi_accounts = TransferChecked {
+      from: ctx.accounts.partner_pda_ata.to_account_info(),
+      mint: mint.to_account_info(),
+      to: ctx.accounts.bank_ata.to_account_info(),
+      authority: partner.to_account_info(),
+    };
+    token_interface::transfer_checked(CpiContext::new_with_signer(program.to_account_info(), cpi_accounts, signer_seeds), amount, mint.decimals).unwrap();
+  }
+
+  emit!(events::PartnerTokenRewardSwept {
+    partner: partner_code,
+    mint: mint.key(),
+    amount: amount,
+  });
+
+  Ok(())
+}
+
+/// Pushes pending SOL, USDC and USDT rewards of a batch of partners to their owner wallets
+pub fn payout_partner_rewards<'info>(
+  ctx: Context<'_, '_, 'info, 'info, PayoutPartnerRewards<'info>>,
+  partner_codes: Vec<String>,
+) -> Result<()> {
+  let sale_handler = &ctx.accounts.sale_handler;
+  let program = &ctx.accounts.token_program;
+
+  check_claim_period(sale_handler)?;
+
+  if ctx.remaining_accounts.len() != partner_codes.len() * PAYOUT_ACCOUNTS_PER_PARTNER {
+    return err!(errors::SaleHandler::PayoutAccountsMismatch);
+  }
+
+  let accounts = ctx.remaining_accounts.chunks(PAYOUT_ACCOUNTS_PER_PARTNER);
+  for (partner_code, accounts) in partner_codes.iter().zip(accounts) {
+    let (partner_key, bump) = Pubkey::find_program_address(&[PARTNER_TAG, b"_", partner_code.as_ref()], &crate::ID);
+    if accounts[0].key() != partner_key {
+      return err!(errors::SaleHandler::PayoutAccountsMismatch);
+    }
+
+    let mut partner: Account<Partner> = Account::try_from(&accounts[0])?;
+    // Partners that cannot be paid are left for a later batch instead of failing this one,
+    // ownerless partners have no wallet to pay and claim through signatures
+    if check_partner_claim(sale_handler, &partner, partner_code).is_err() || partner.get_owner() == Pubkey::default() {
+      emit!(events::PartnerPayoutSkipped {
+        partner: partner_code.clone(),
+        currency: None,
+      });
+
+      continue;
+    }
+
+    let owner = &accounts[1];
+    if !partner.is_owner(owner.key()) {
+      return err!(errors::SaleHandler::PayoutAccountsMismatch);
+    }
+
+    let sol_amount = partner.get_sol_reward();
+    if sol_amount > 0 {
+      partner.reset_sol_reward().unwrap();
+      withdraw_lamports(&accounts[0], owner, sol_amount)?;
+
+      emit!(events::ReceiveSol {
+        partner: partner_code.clone(),
+        amount: sol_amount,
+      });
+    }
+
+    let bump = &[bump];
+    let seeds: &[&[u8]] = &[PARTNER_TAG, b"_", partner_code.as_ref(), bump];
+    let signer_seeds = &[seeds];
+
+    let usdc_amount = partner.get_usdc_reward();
+    if usdc_amount > 0 {
+      let mint = &ctx.accounts.usdc_mint;
+      check_payout_atas(partner_key, owner.key(), mint, &accounts[2], &accounts[3])?;
+
+      if is_token_account(&accounts[3]) {
+        partner.reset_usdc_reward().unwrap();
+
+        let cpi_accounts = TransferChecked {
+          from: accounts[2].to_account_info(),
+          mint: mint.to_account_info(),
+          to: accounts[3].to_account_info(),
+          authority: accounts[0].to_account_info(),
+        };
+        token_interface::transfer_checked(CpiContext::new_with_signer(program.to_account_info(), cpi_accounts, signer_seeds), usdc_amount, mint.decimals).unwrap();
+
+        emit!(events::ReceiveUsdc {
+          partner: partner_code.clone(),
+          amount: usdc_amount,
+        });
+      } else {
+        emit!(events::PartnerPayoutSkipped {
+          partner: partner_code.clone(),
+          currency: Some(ClaimCurrency::Usdc.to_string()),
+        });
+      }
+    }
+
+    let usdt_amount = partner.get_usdt_reward();
+    if usdt_amount > 0 {
+      let mint = &ctx.accounts.usdt_mint;
+      check_payout_atas(partner_key, owner.key(), mint, &accounts[4], &accounts[5])?;
+
+      if is_token_account(&accounts[5]) {
+        partner.reset_usdt_reward().unwrap();
+
+        let cpi_accounts = TransferChecked {
+          from: accounts[4].to_account_info(),
+          mint: mint.to_account_info(),
+          to: accounts[5].to_account_info(),
+          authority: accounts[0].to_account_info(),
+        };
+        token_interface::transfer_checked(CpiContext::new_with_signer(program.to_account_info(), cpi_accounts, signer_seeds), usdt_amount, mint.decimals).unwrap();
+
+        emit!(events::ReceiveUsdt {
+          partner: partner_code.clone(),
+          amount: usdt_amount,
+        });
+      } else {
+        emit!(events::PartnerPayoutSkipped {
+          partner: partner_code.clone(),
+          currency: Some(ClaimCurrency::Usdt.to_string()),
+        });
+      }
+    }
+
+    partner.exit(&crate::ID)?;
+  }
+
+  Ok(())
+}
+
+/// Pushes pending accepted token rewards of a batch of partners to their owner wallets
+pub fn payout_partner_token_rewards<'info>(
+  ctx: Context<'_, '_, 'info, 'info, PayoutPartnerTokenRewards<'info>>,
+  partner_codes: Vec<String>,
+) -> Result<()> {
+  let sale_handler = &ctx.accounts.sale_handler;
+  let mint = &ctx.accounts.mint;
+  let program = &ctx.accounts.token_program;
+
+  check_claim_period(sale_handler)?;
+
+  if ctx.remaining_accounts.len() != partner_codes.len() * PAYOUT_TOKEN_ACCOUNTS_PER_PARTNER {
+    return err!(errors::SaleHandler::PayoutAccountsMismatch);
+  }
+
+  let accounts = ctx.remaining_accounts.chunks(PAYOUT_TOKEN_ACCOUNTS_PER_PARTNER);
+  for (partner_code, accounts) in partner_codes.iter().zip(accounts) {
+    let (partner_key, bump) = Pubkey::find_program_address(&[PARTNER_TAG, b"_", partner_code.as_ref()], &crate::ID);
+    let (partner_reward_key, _) = Pubkey::find_program_address(&[PARTNER_REWARD_TAG, b"_", partner_key.as_ref(), mint.key().as_ref()], &crate::ID);
+    if accounts[0].key() != partner_key || accounts[2].key() != partner_reward_key {
+      return err!(errors::SaleHandler::PayoutAccountsMismatch);
+    }
+
+    let partner: Account<Partner> = Account::try_from(&accounts[0])?;
+    // Partners that cannot be paid are left for a later batch instead of failing this one,
+    // ownerless partners have no wallet to pay and claim through signatures
+    if check_partner_claim(sale_handler, &partner, partner_code).is_err() || partner.get_owner() == Pubkey::default() {
+      emit!(events::PartnerPayoutSkipped {
+        partner: partner_code.clone(),
+        currency: None,
+      });
+
+      continue;
+    }
+
+    let owner = &accounts[1];
+    if !partner.is_owner(owner.key()) {
+      return err!(errors::SaleHandler::PayoutAccountsMismatch);
+    }
+
+    let mut partner_reward: Account<PartnerTokenReward> = Account::try_from(&accounts[2])?;
+    let amount = partner_reward.get_reward();
+    if amount == 0 {
+      continue;
+    }
+
+    check_payout_atas(partner_key, owner.key(), mint, &accounts[3], &accounts[4])?;
+    if !is_token_account(&accounts[4]) {
+      emit!(events::PartnerPayoutSkipped {
+        partner: partner_code.clone(),
+        currency: Some(ClaimCurrency::Spl(mint.key()).to_string()),
+      });
+
+      continue;
+    }
+
+    partner_reward.reset_reward().unwrap();
+    partner_reward.exit(&crate::ID)?;
+
+    let bump = &[bump];
+    let seeds: &[&[u8]] = &[PARTNER_TAG, b"_", partner_code.as_ref(), bump];
+    let signer_seeds = &[seeds];
+
+    let cpi_accounts = TransferChecked {
+      from: accounts[3].to_account_info(),
+      mint: mint.to_account_info(),
+      to: accounts[4].to_account_info(),
+      authority: accounts[0].to_account_info(),
+    };
+    token_interface::transfer_checked(CpiContext::new_with_signer(program.to_account_info(), cpi_accounts, signer_seeds), amount, mint.decimals).unwrap();
+
+    emit!(events::ReceivePaymentToken {
+      partner: partner_code.clone(),
+      mint: mint.key(),
+      amount: amount,
+    });
+  }
+
+  Ok(())
+}
+
+/// Partner and owner token accounts of a payout must be the canonical ATAs of `mint`
+fn check_payout_atas(
+  partner: Pubkey,
+  owner: Pubkey,
+  mint: &InterfaceAccount<Mint>,
+  partner_ata: &AccountInfo,
+  owner_ata: &AccountInfo,
+) -> Result<()> {
+  let token_program = mint.to_account_info().owner;
+  let partner_ata_key = get_associated_token_address_with_program_id(&partner, &mint.key(), token_program);
+  let owner_ata_key = get_associated_token_address_with_program_id(&owner, &mint.key(), token_program);
+  if partner_ata.key() != partner_ata_key || owner_ata.key() != owner_ata_key {
+    return err!(errors::SaleHandler::PayoutAccountsMismatch);
+  }
+
+  Ok(())
+}
+
+/// Owner token accounts are not created by payouts, an uninitialized one skips the currency
+fn is_token_account<'info>(
+  account: &'info AccountInfo<'info>,
+) -> bool {
+  InterfaceAccount::<TokenAccount>::try_from(account).is_ok()
+}
+
+/// Lowercase letters, digits, `-` and `_`, within seed length and outside of the reserved list
+fn validate_partner_code(
+  partner_code: &str,
+) -> Result<()> {
+  if partner_code.len() < PARTNER_CODE_MIN_LEN || partner_code.len() > PARTNER_CODE_MAX_LEN {
+    return err!(errors::SaleHandler::WrongPartnerCodeLength);
+  }
+
+  let valid_charset = partner_code.bytes().all(|c| {
+    c.is_ascii_lowercase() || c.is_ascii_digit() || c == b'-' || c == b'_'
+  });
+  if !valid_charset {
+    return err!(errors::SaleHandler::WrongPartnerCodeCharset);
+  }
+
+  if RESERVED_PARTNER_CODES.contains(&partner_code) {
+    return err!(errors::SaleHandler::ReservedPartnerCode);
+  }
+
+  Ok(())
+}
+
+/// Pending rewards of a disabled partner were all earned before disabling, as purchases stop accruing them
+fn check_partner_claim(
+  sale_handler: &SaleHandler,
+  partner: &Partner,
+  partner_code: &str,
+) -> Result<()> {
+  if partner.is_enabled() {
+    return Ok(());
+  }
+
+  if sale_handler.get_disabled_partner_claim_policy() == DisabledPartnerClaimPolicy::Block {
+    return err!(errors::SaleHandler::PartnerNotEnabled);
+  }
+
+  emit!(events::DisabledPartnerClaim {
+    partner: partner_code.to_string(),
+  });
+
+  Ok(())
+}
+
+#[derive(Accounts)]
+#[instruction(partner_code: String)]
+pub struct InitPartner<'info> {
+  #[account(
+    init,
+    payer = payer,
+    space = 8 + Partner::MAX_SIZE,
+    seeds = [
+      PARTNER_TAG,
+      b"_",
+      partner_code.as_ref()
+    ],
+    bump
+  )]
+  pub partner: Account<'info, Partner>,
+  #[account(mut)]
+  pub payer: Signer<'info>,
+  pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(partner_code: String)]
+pub struct RegisterPartner<'info> {
+  #[account(
+    init,
+    payer = payer,
+    space = 8 + Partner::MAX_SIZE,
+    seeds = [
+      PARTNER_TAG,
+      b"_",
+      partner_code.as_ref()
+    ],
+    bump
+  )]
+  pub partner: Account<'info, Partner>,
+  #[account(
+    seeds = [],
+    bump,
+  )]
+  pub sale_handler: Account<'info, SaleHandler>,
+  #[account(
+    seeds = [SIGNER_SET_TAG],
+    bump,
+  )]
+  pub signer_set: Option<Account<'info, SignerSet>>,
+
+  #[account(address = IX_ID)]
+  /// CHECK: we need this for sign
+  pub ix_sysvar: AccountInfo<'info>,
+
+  #[account(mut)]
+  pub payer: Signer<'info>,
+  pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(partner_code: String, new_owner: Pubkey)]
+pub struct TransferPartnerOwnership<'info> {
+  #[account(
+    mut,
+    seeds = [
+      PARTNER_TAG,
+      b"_",
+      partner_code.as_ref()
+    ],
+    bump
+  )]
+  pub partner: Account<'info, Partner>,
+  #[account(mut)]
+  pub payer: Signer<'info>,
+}
+
+#[derive(Accounts)]
+#[instruction(signed_claims: bool)]
+pub struct SetPartnerSignedClaims<'info> {
+  #[account(mut)]
+  pub partner: Account<'info, Partner>,
+  #[account(mut)]
+  pub payer: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct SetPartnerInterest<'info> {
+  #[account(mut)]
+  pub partner: Account<'info, Partner>,
+  #[account(mut)]
+  pub payer: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct SetPartnerParent<'info> {
+  #[account(mut)]
+  pub partner: Account<'info, Partner>,
+  #[account(
+    constraint = parent.key() != partner.key() @ errors::SaleHandler::ReferralCycle,
+    constraint = parent.get_parent() != partner.key() @ errors::SaleHandler::ReferralCycle,
+  )]
+  pub parent: Option<Account<'info, Partner>>,
+  #[account(mut)]
+  pub payer: Signer<'info>,
+}
+
+#[derive(Accounts)]
+#[instruction(buyer_bonus: u64)]
+pub struct SetPartnerBuyerBonus<'info> {
+  #[account(mut)]
+  pub partner: Account<'info, Partner>,
+  #[account(
+    seeds = [],
+    bump,
+  )]
+  pub sale_handler: Account<'info, SaleHandler>,
+  #[account(mut)]
+  pub payer: Signer<'info>,
+}
+
+#[derive(Accounts)]
+#[instruction(wallet: Pubkey, partner_code: String)]
+pub struct SetPurchaserPartner<'info> {
+  #[account(
+    mut,
+    seeds = [
+      PURCHASER_TAG,
+      b"_",
+      wallet.as_ref()
+    ],
+    bump
+  )]
+  pub purchaser: Account<'info, Purchaser>,
+  #[account(
+    seeds = [
+      PARTNER_TAG,
+      b"_",
+      partner_code.as_ref()
+    ],
+    bump
+  )]
+  pub partner: Option<Account<'info, Partner>>,
+  #[account(mut)]
+  pub payer: Signer<'info>,
+}
+
+#[derive(Accounts)]
+#[instruction(linked_wallets: Vec<Pubkey>, commission_cap_usd: u128)]
+pub struct SetPartnerLimits<'info> {
+  #[account(mut)]
+  pub partner: Account<'info, Partner>,
+  #[account(mut)]
+  pub payer: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct SetPartnerEnabled<'info> {
+  #[account(mut)]
+  pub partner: Account<'info, Partner>,
+  #[account(mut)]
+  pub payer: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct SetPartnerDisabled<'info> {
+  #[account(mut)]
+  pub partner: Account<'info, Partner>,
+  #[account(mut)]
+  pub payer: Signer<'info>,
+}
+
+#[derive(Accounts)]
+#[instruction(partner_code: String)]
+pub struct ReceiveSol<'info> {
+  #[account(
+    mut,
+    seeds = [
+      PARTNER_TAG,
+      b"_",
+      partner_code.as_ref()
+    ],
+    bump
+  )]
+  pub partner: Account<'info, Partner>,
+  #[account(
+    seeds = [],
+    bump,
+  )]
+  pub sale_handler: Account<'info, SaleHandler>,
+
+  #[account(
+    seeds = [SIGNER_SET_TAG],
+    bump,
+  )]
+  pub signer_set: Option<Account<'info, SignerSet>>,
+
+  #[account(address = IX_ID)]
+  /// CHECK: we need this for sign
+  pub ix_sysvar: AccountInfo<'info>,
+
+  #[account(mut)]
+  pub payer: Signer<'info>,
+}
+
+#[derive(Accounts)]
+#[instruction(partner_code: String)]
+pub struct ReceiveUSDC<'info> {
+  #[account(
+    mut,
+    seeds = [
+      PARTNER_TAG,
+      b"_",
+      partner_code.as_ref()
+    ],
+    bump
+  )]
+  pub partner: Account<'info, Partner>,
+  #[account(
+    seeds = [],
+    bump,
+  )]
+  pub sale_handler: Account<'info, SaleHandler>,
+  #[account(address = USDC.parse::<Pubkey>().unwrap())]
+  pub mint: InterfaceAccount<'info, Mint>,
+  #[account(
+    mut,
+    constraint = partner_ata.mint == USDC.parse::<Pubkey>().unwrap(),
+    constraint = partner_ata.owner == payer.key(),
+  )]
+  pub partner_ata: InterfaceAccount<'info, TokenAccount>,
+  #[account(
+    mut,
+    constraint = partner_pda_ata.mint == USDC.parse::<Pubkey>().unwrap(),
+    constraint = partner_pda_ata.owner == partner.key(),
+  )]
+  pub partner_pda_ata: InterfaceAccount<'info, TokenAccount>,
+  pub token_program: Interface<'info, TokenInterface>,
+
+  #[account(
+    seeds = [SIGNER_SET_TAG],
+    bump,
+  )]
+  pub signer_set: Option<Account<'info, SignerSet>>,
+
+  #[account(address = IX_ID)]
+  /// CHECK: we need this for sign
+  pub ix_sysvar: AccountInfo<'info>,
+
+  #[account(mut)]
+  pub payer: Signer<'info>,
+}
+
+#[derive(Accounts)]
+#[instruction(partner_code: String)]
+pub struct ReceiveUSDT<'info> {
+  #[account(
+    mut,
+    seeds = [
+      PARTNER_TAG,
+      b"_",
+      partner_code.as_ref()
+    ],
+    bump
+  )]
+  pub partner: Account<'info, Partner>,
+  #[account(
+    seeds = [],
+    bump,
+  )]
+  pub sale_handler: Account<'info, SaleHandler>,
+  #[account(address = USDT.parse::<Pubkey>().unwrap())]
+  pub mint: InterfaceAccount<'info, Mint>,
+  #[account(
+    mut,
+    constraint = partner_ata.mint == USDT.parse::<Pubkey>().unwrap(),
+    constraint = partner_ata.owner == payer.key(),
+  )]
+  pub partner_ata: InterfaceAccount<'info, TokenAccount>,
+  #[account(
+    mut,
+    constraint = partner_pda_ata.mint == USDT.parse::<Pubkey>().unwrap(),
+    constraint = partner_pda_ata.owner == partner.key(),
+  )]
+  pub partner_pda_ata: InterfaceAccount<'info, TokenAccount>,
+  pub token_program: Interface<'info, TokenInterface>,
+
+  #[account(
+    seeds = [SIGNER_SET_TAG],
+    bump,
+  )]
+  pub signer_set: Option<Account<'info, SignerSet>>,
+
+  #[account(address = IX_ID)]
+  /// CHECK: we need this for sign
+  pub ix_sysvar: AccountInfo<'info>,
+
+  #[account(mut)]
+  pub payer: Signer<'info>,
+}
+
+#[derive(Accounts)]
+#[instruction(partner_code: String)]
+pub struct ReceivePaymentToken<'info> {
+  #[account(
+    mut,
+    seeds = [
+      PARTNER_TAG,
+      b"_",
+      partner_code.as_ref()
+    ],
+    bump
+  )]
+  pub partner: Account<'info, Partner>,
+  #[account(
+    mut,
+    seeds = [
+      PARTNER_REWARD_TAG,
+      b"_",
+      partner.key().as_ref(),
+      mint.key().as_ref()
+    ],
+    bump
+  )]
+  pub partner_reward: Account<'info, PartnerTokenReward>,
+  #[account(
+    seeds = [],
+    bump,
+  )]
+  pub sale_handler: Account<'info, SaleHandler>,
+  pub mint: InterfaceAccount<'info, Mint>,
+  #[account(
+    mut,
+    constraint = partner_ata.mint == mint.key(),
+    constraint = partner_ata.owner == payer.key(),
+  )]
+  pub partner_ata: InterfaceAccount<'info, TokenAccount>,
+  #[account(
+    mut,
+    associated_token::mint = mint,
+    associated_token::authority = partner,
+    associated_token::token_program = token_program,
+  )]
+  pub partner_pda_ata: InterfaceAccount<'info, TokenAccount>,
+  pub token_program: Interface<'info, TokenInterface>,
+
+  #[account(
+    seeds = [SIGNER_SET_TAG],
+    bump,
+  )]
+  pub signer_set: Option<Account<'info, SignerSet>>,
+
+  #[account(address = IX_ID)]
+  /// CHECK: we need this for sign
+  pub ix_sysvar: AccountInfo<'info>,
+
+  #[account(mut)]
+  pub payer: Signer<'info>,
+}
+
+#[derive(Accounts)]
+#[instruction(partner_code: String)]
+pub struct ReceiveTokens<'info> {
+  #[account(
+    mut,
+    seeds = [
+      PARTNER_TAG,
+      b"_",
+      partner_code.as_ref()
+    ],
+    bump
+  )]
+  pub partner: Account<'info, Partner>,
+  #[account(
+    seeds = [],
+    bump,
+  )]
+  pub sale_handler: Account<'info, SaleHandler>,
+  #[account(address = sale_handler.get_token_mint())]
+  pub mint: InterfaceAccount<'info, Mint>,
+  #[account(
+    mut,
+    constraint = token_vault.mint == sale_handler.get_token_mint(),
+    constraint = token_vault.owner == sale_handler.key(),
+  )]
+  pub token_vault: InterfaceAccount<'info, TokenAccount>,
+  #[account(
+    mut,
+    constraint = partner_token_ata.mint == sale_handler.get_token_mint(),
+    constraint = partner_token_ata.owner == payer.key(),
+  )]
+  pub partner_token_ata: InterfaceAccount<'info, TokenAccount>,
+  pub token_program: Interface<'info, TokenInterface>,
+
+  #[account(
+    seeds = [SIGNER_SET_TAG],
+    bump,
+  )]
+  pub signer_set: Option<Account<'info, SignerSet>>,
+
+  #[account(address = IX_ID)]
+  /// CHECK: we need this for sign
+  pub ix_sysvar: AccountInfo<'info>,
+
+  #[account(mut)]
+  pub payer: Signer<'info>,
+}
+
+#[derive(Accounts)]
+#[instruction(partner_code: String)]
+pub struct SweepPartnerRewards<'info> {
+  #[account(
+    mut,
+    seeds = [
+      PARTNER_TAG,
+      b"_",
+      partner_code.as_ref()
+    ],
+    bump
+  )]
+  pub partner: Account<'info, Partner>,
+  #[account(
+    seeds = [],
+    bump,
+  )]
+  pub sale_handler: Account<'info, SaleHandler>,
+  #[account(mut)]
+  /// CHECK: bank info
+  pub bank_info: AccountInfo<'info>,
+  #[account(address = USDC.parse::<Pubkey>().unwrap())]
+  pub usdc_mint: Option<InterfaceAccount<'info, Mint>>,
+  #[account(address = USDT.parse::<Pubkey>().unwrap())]
+  pub usdt_mint: Option<InterfaceAccount<'info, Mint>>,
+  #[account(
+    mut,
+    constraint = partner_pda_usdc_ata.mint == USDC.parse::<Pubkey>().unwrap(),
+    constraint = partner_pda_usdc_ata.owner == partner.key(),
+  )]
+  pub partner_pda_usdc_ata: Option<InterfaceAccount<'info, TokenAccount>>,
+  #[account(
+    mut,
+    constraint = bank_usdc_ata.mint == USDC.parse::<Pubkey>().unwrap(),
+    constraint = bank_usdc_ata.owner == BANK.parse::<Pubkey>().unwrap(),
+  )]
+  pub bank_usdc_ata: Option<InterfaceAccount<'info, TokenAccount>>,
+  #[account(
+    mut,
+    constraint = partner_pda_usdt_ata.mint == USDT.parse::<Pubkey>().unwrap(),
+    constraint = partner_pda_usdt_ata.owner == partner.key(),
+  )]
+  pub partner_pda_usdt_ata: Option<InterfaceAccount<'info, TokenAccount>>,
+  #[account(
+    mut,
+    constraint = bank_usdt_ata.mint == USDT.parse::<Pubkey>().unwrap(),
+    constraint = bank_usdt_ata.owner == BANK.parse::<Pubkey>().unwrap(),
+  )]
+  pub bank_usdt_ata: Option<InterfaceAccount<'info, TokenAccount>>,
+  pub token_program: Interface<'info, TokenInterface>,
+  #[account(mut)]
+  pub payer: Signer<'info>,
+}
+
+#[derive(Accounts)]
+#[instruction(partner_code: String)]
+pub struct SweepPartnerTokenReward<'info> {
+  #[account(
+    seeds = [
+      PARTNER_TAG,
+      b"_",
+      partner_code.as_ref()
+    ],
+    bump
+  )]
+  pub partner: Account<'info, Partner>,
+  #[account(
+    mut,
+    seeds = [
+      PARTNER_REWARD_TAG,
+      b"_",
+      partner.key().as_ref(),
+      mint.key().as_ref()
+    ],
+    bump
+  )]
+  pub partner_reward: Account<'info, PartnerTokenReward>,
+  #[account(
+    seeds = [],
+    bump,
+  )]
+  pub sale_handler: Account<'info, SaleHandler>,
+  pub mint: InterfaceAccount<'info, Mint>,
+  #[account(
+    mut,
+    associated_token::mint = mint,
+    associated_token::authority = partner,
+    associated_token::token_program = token_program,
+  )]
+  pub partner_pda_ata: InterfaceAccount<'info, TokenAccount>,
+  #[account(
+    mut,
+    constraint = bank_ata.mint == mint.key(),
+    constraint = bank_ata.owner == BANK.parse::<Pubkey>().unwrap(),
+  )]
+  pub bank_ata: InterfaceAccount<'info, TokenAccount>,
+  pub token_program: Interface<'info, TokenInterface>,
+  #[account(mut)]
+  pub payer: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct PayoutPartnerRewards<'info> {
+  #[account(
+    seeds = [],
+    bump,
+  )]
+  pub sale_handler: Account<'info, SaleHandler>,
+  #[account(address = USDC.parse::<Pubkey>().unwrap())]
+  pub usdc_mint: InterfaceAccount<'info, Mint>,
+  #[account(address = USDT.parse::<Pubkey>().unwrap())]
+  pub usdt_mint: InterfaceAccount<'info, Mint>,
+  pub token_program: Interface<'info, TokenInterface>,
+  #[account(mut)]
+  pub payer: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct PayoutPartnerTokenRewards<'info> {
+  #[account(
+    seeds = [],
+    bump,
+  )]
+  pub sale_handler: Account<'info, SaleHandler>,
+  pub mint: InterfaceAccount<'info, Mint>,
+  pub token_program: Interface<'info, TokenInterface>,
+  #[account(mut)]
+  pub payer: Signer<'info>,
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn validate_err(partner_code: &str) -> Error {
+    match validate_partner_code(partner_code) {
+      Ok(()) => panic!("expected an error"),
+      Err(error) => error,
+    }
+  }
+
+  #[test]
+  fn accepts_valid_codes() {
+    assert!(validate_partner_code("abc").is_ok());
+    assert!(validate_partner_code("partner-01_x").is_ok());
+    assert!(validate_partner_code(&"a".repeat(PARTNER_CODE_MAX_LEN)).is_ok());
+  }
+
+  #[test]
+  fn rejects_wrong_length() {
+    assert_eq!(validate_err(""), errors::SaleHandler::WrongPartnerCodeLength.into());
+    assert_eq!(validate_err(&"a".repeat(PARTNER_CODE_MIN_LEN - 1)), errors::SaleHandler::WrongPartnerCodeLength.into());
+    assert_eq!(validate_err(&"a".repeat(PARTNER_CODE_MAX_LEN + 1)), errors::SaleHandler::WrongPartnerCodeLength.into());
+  }
+
+  #[test]
+  fn rejects_wrong_charset() {
+    assert_eq!(validate_err("Partner"), errors::SaleHandler::WrongPartnerCodeCharset.into());
+    assert_eq!(validate_err("part ner"), errors::SaleHandler::WrongPartnerCodeCharset.into());
+    assert_eq!(validate_err("part.ner"), errors::SaleHandler::WrongPartnerCodeCharset.into());
+    assert_eq!(validate_err("partnér"), errors::SaleHandler::WrongPartnerCodeCharset.into());
+  }
+
+  #[test]
+  fn rejects_reserved_codes() {
+    for partner_code in RESERVED_PARTNER_CODES {
+      assert_eq!(validate_err(partner_code), errors::SaleHandler::ReservedPartnerCode.into());
+    }
+  }
 }
@@ -0,0 +1,72 @@
+use anchor_lang::prelude::*;
+use crate::errors;
+use crate::state::promo_code::{ PromoCode, PromoCodeParams, PromoRedemption };
+
+use crate::config::PROMO_CODE_TAG;
+
+pub fn init_promo_code(
+  ctx: Context<InitPromoCode>,
+  params: PromoCodeParams,
+) -> Result<()> {
+  let promo = &mut ctx.accounts.promo;
+  promo.init(params)
+}
+
+pub fn disable_promo_code(
+  ctx: Context<SetPromoCodeDisabled>,
+) -> Result<()> {
+  let promo = &mut ctx.accounts.promo;
+  promo.disable()
+}
+
+/// Bonus tokens of the promo code, counting the redemption globally and for the wallet
+pub fn redeem_promo_code(
+  promo_code: &str,
+  promo: Option<&mut Account<PromoCode>>,
+  redemption: Option<&mut Account<PromoRedemption>>,
+  step: i16,
+  token_amount: u128,
+) -> Result<u128> {
+  if promo_code.is_empty() {
+    return Ok(0);
+  }
+
+  let (promo, redemption) = match (promo, redemption) {
+    (Some(promo), Some(redemption)) => (promo, redemption),
+    _ => return err!(errors::SaleHandler::PromoCodeNotFound),
+  };
+
+  let clock: Clock = Clock::get()?;
+  promo.redeem(step, clock.unix_timestamp)?;
+  redemption.redeem(promo.get_max_wallet_redemptions())?;
+
+  Ok(promo.calculate_bonus(token_amount))
+}
+
+#[derive(Accounts)]
+#[instruction(promo_code: String)]
+pub struct InitPromoCode<'info> {
+  #[account(
+    init,
+    payer = payer,
+    space = 8 + PromoCode::MAX_SIZE,
+    seeds = [
+      PROMO_CODE_TAG,
+      b"_",
+      promo_code.as_ref()
+    ],
+    bump
+  )]
+  pub promo: Account<'info, PromoCode>,
+  #[account(mut)]
+  pub payer: Signer<'info>,
+  pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct SetPromoCodeDisabled<'info> {
+  #[account(mut)]
+  pub promo: Account<'info, PromoCode>,
+  #[account(mut)]
+  pub payer: Signer<'info>,
+}
@@ -1,22 +1,33 @@
 use anchor_lang::{
   prelude::*,
-  solana_program::{ program::invoke, system_instruction::transfer },
+  Discriminator,
+  solana_program::{ program::{ invoke, invoke_signed }, system_instruction::{ allocate, assign, transfer } },
 };
 use std::str::FromStr;
-use anchor_spl::token::{ self, Token, TokenAccount, Transfer as SplTransfer };
+use anchor_spl::token_interface::{ self, get_mint_extension_data, Mint, TokenAccount, TokenInterface, TransferChecked };
+use anchor_spl::token_interface::spl_token_2022::extension::transfer_fee::TransferFeeConfig;
+use anchor_spl::associated_token::{ self, get_associated_token_address_with_program_id, AssociatedToken };
 use pyth_solana_receiver_sdk::price_update::{ get_feed_id_from_hex, PriceUpdateV2 };
 
 use crate::errors;
 use crate::events;
-use crate::state::sale_handler::SaleHandler;
+use crate::state::sale_handler::{ SaleHandler, DisabledPartnerPurchasePolicy, DisabledPartnerClaimPolicy, PartnerRegistration, SelfReferralPolicy, SettlementMode };
 use crate::state::step::Step;
 use crate::state::partner::Partner;
 use crate::state::purchaser::Purchaser;
+use crate::state::promo_code::{ PromoCode, PromoRedemption };
+use crate::state::escrow::Escrow;
+use crate::state::accepted_token::AcceptedToken;
+use crate::state::partner_token_reward::PartnerTokenReward;
+use crate::state::purchase_record::PurchaseRecord;
+use crate::instructions::promo_code::redeem_promo_code;
+use crate::instructions::escrow::get_escrow;
 
 use crate::config::{
-  SOL_USD_PRICEFEED, BANK, USDC, USDT,
-  PRECISION, STABLE_PRECISION, PARTNER_TAG,
+  SOL_USD_PRICEFEED, BANK,
+  PRECISION, PARTNER_TAG,
   PURCHASER_TAG, FEED_MAXIMUM_AGE, FEED_ID,
+  PROMO_CODE_TAG, ESCROW_TAG, ACCEPTED_TOKEN_TAG, PARTNER_REWARD_TAG, PURCHASE_RECORD_TAG,
 };
 
 pub fn init_sale_handler(
@@ -53,6 +64,94 @@ pub fn set_sale_handler_purchase_bonus(
   sale_handler.set_bonus(thresholds, percents)
 }
 
+pub fn set_sale_handler_tge(
+  ctx: Context<SetSaleHandlerTge>,
+  token_mint: Pubkey,
+  tge_timestamp: i64,
+  tge_percent: u64,
+  vesting_period: i64,
+) -> Result<()> {
+  let sale_handler = &mut ctx.accounts.sale_handler;
+  sale_handler.set_tge(token_mint, tge_timestamp, tge_percent, vesting_period)
+}
+
+pub fn set_sale_handler_partner_policy(
+  ctx: Context<SetSaleHandlerPartnerPolicy>,
+  purchase_policy: DisabledPartnerPurchasePolicy,
+  claim_policy: DisabledPartnerClaimPolicy,
+) -> Result<()> {
+  let sale_handler = &mut ctx.accounts.sale_handler;
+  sale_handler.set_partner_policy(purchase_policy, claim_policy)
+}
+
+pub fn set_sale_handler_partner_registration(
+  ctx: Context<SetSaleHandlerPartnerRegistration>,
+  partner_registration: PartnerRegistration,
+) -> Result<()> {
+  let sale_handler = &mut ctx.accounts.sale_handler;
+  sale_handler.set_partner_registration(partner_registration)
+}
+
+pub fn set_sale_handler_referral_interests(
+  ctx: Context<SetSaleHandlerReferralInterests>,
+  interests: Vec<u64>,
+) -> Result<()> {
+  let sale_handler = &mut ctx.accounts.sale_handler;
+  sale_handler.set_referral_interests(interests)
+}
+
+pub fn set_sale_handler_partner_tiers(
+  ctx: Context<SetSaleHandlerPartnerTiers>,
+  thresholds: Vec<u64>,
+  main_interests: Vec<u64>,
+  secondary_interests: Vec<u64>,
+) -> Result<()> {
+  let sale_handler = &mut ctx.accounts.sale_handler;
+  sale_handler.set_partner_tiers(thresholds, main_interests, secondary_interests)
+}
+
+pub fn set_sale_handler_referral_budget(
+  ctx: Context<SetSaleHandlerReferralBudget>,
+  referral_budget: u64,
+) -> Result<()> {
+  let sale_handler = &mut ctx.accounts.sale_handler;
+  sale_handler.set_referral_budget(referral_budget)
+}
+
+pub fn set_sale_handler_self_referral_policy(
+  ctx: Context<SetSaleHandlerSelfReferralPolicy>,
+  self_referral_policy: SelfReferralPolicy,
+) -> Result<()> {
+  let sale_handler = &mut ctx.accounts.sale_handler;
+  sale_handler.set_self_referral_policy(self_referral_policy)
+}
+
+pub fn set_sale_handler_settlement(
+  ctx: Context<SetSaleHandlerSettlement>,
+  settlement_mode: SettlementMode,
+  holding_period: i64,
+) -> Result<()> {
+  let sale_handler = &mut ctx.accounts.sale_handler;
+  sale_handler.set_settlement(settlement_mode, holding_period)
+}
+
+pub fn set_sale_handler_claim_period(
+  ctx: Context<SetSaleHandlerClaimPeriod>,
+  claim_period: i64,
+) -> Result<()> {
+  let sale_handler = &mut ctx.accounts.sale_handler;
+  sale_handler.set_claim_period(claim_period)
+}
+
+pub fn set_sale_handler_depeg_guard(
+  ctx: Context<SetSaleHandlerDepegGuard>,
+  depeg_threshold: u64,
+  depeg_limit: u64,
+) -> Result<()> {
+  let sale_handler = &mut ctx.accounts.sale_handler;
+  sale_handler.set_depeg_guard(depeg_threshold, depeg_limit)
+}
+
 pub fn enable_sale_handler(
   ctx: Context<SetSaleHandlerEnabled>,
 ) -> Result<()> {
@@ -64,22 +163,28 @@ pub fn disable_sale_handler(
   ctx: Context<SetSaleHandlerDisabled>,
 ) -> Result<()> {
   let sale_handler = &mut ctx.accounts.sale_handler;
-  sale_handler.set_disable()
+  let clock: Clock = Clock::get()?;
+  sale_handler.set_disable(clock.unix_timestamp)
 }
 
-pub fn purchase_with_sol(
-  ctx: Context<PurchaseSol>,
+pub fn purchase_with_sol<'info>(
+  ctx: Context<'_, '_, 'info, 'info, PurchaseSol<'info>>,
   partner_code: String,
   amount: u64,
+  promo_code: String,
 ) -> Result<()> {
   let to_account_infos = &mut ctx.accounts.to_account_infos();
   let payer = &mut ctx.accounts.payer;
   let sale_handler = &mut ctx.accounts.sale_handler;
   let step = &mut ctx.accounts.step;
   let purchaser = &mut ctx.accounts.purchaser;
-  let partner = &mut ctx.accounts.partner;
+  // A bound buyer keeps its partner whatever code comes with the purchase
+  let partner_code = purchaser.get_referral_code(&partner_code).to_string();
+  let mut partner = get_partner(sale_handler, purchaser, &partner_code, &mut ctx.accounts.partner)?;
+  bind_partner(purchaser, partner.as_deref_mut(), &partner_code, payer.key())?;
   let price_update = &ctx.accounts.price_update;
   let bank_info = &mut ctx.accounts.bank_info;
+  let system_program = &ctx.accounts.system_program;
 
   if !sale_handler.is_enabled() {
     return err!(errors::SaleHandler::SaleHandlerNotEnabled);
@@ -104,7 +209,8 @@ pub fn purchase_with_sol(
   let (price, expo) = get_price(price_update).unwrap();
   let usd_amount = u128::from(amount) * price / 10u128.pow(expo);
   let mut token_amount = usd_amount * 10u128.pow(PRECISION) / u128::from(step.get_price());
-  let bonus = sale_handler.calculate_bonus(usd_amount, token_amount);
+  let promo_bonus = redeem_promo_code(&promo_code, ctx.accounts.promo.as_mut(), ctx.accounts.promo_redemption.as_mut(), step.get_id(), token_amount)?;
+  let bonus = sale_handler.calculate_bonus(usd_amount, token_amount) + get_buyer_bonus(sale_handler, partner.as_deref(), payer.key(), token_amount) + promo_bonus;
 
   if sale_handler.get_max_cap() < usd_amount {
     return err!(errors::SaleHandler::SaleHandlerMaxCapExceeded);
@@ -118,20 +224,46 @@ pub fn purchase_with_sol(
     return err!(errors::SaleHandler::StepSupplyExceeded);
   }
   
-  let (partner_sol_reward, partner_token_reward) = get_interest(sale_handler, &partner_code, partner, amount, token_amount).unwrap();
-  let mut to_amount = amount;
-  if partner_sol_reward > 0 {
-    to_amount = to_amount - partner_sol_reward;
+  let reward_usd = |reward: u64| Ok(u128::from(reward) * price / 10u128.pow(expo));
+  let (partner_reward, secondary_interest) = match partner.as_mut() {
+    Some(partner) => get_interest(sale_handler, &partner_code, partner, payer.key(), amount, &reward_usd)?,
+    None => (0, 0),
+  };
+  let partner_token_reward = token_amount * u128::from(secondary_interest) / 10u128.pow(PRECISION);
+  let mut escrow = get_escrow(sale_handler, &mut ctx.accounts.escrow)?;
+  let mut ancestors = get_ancestors(sale_handler, partner.as_deref(), payer.key(), ctx.remaining_accounts, 1, amount, &reward_usd)?;
+  let rewards = get_purchase_rewards(partner.as_deref(), partner_reward, &ancestors);
+  // Clawback debt is paid off first, that part stays with the bank
+  let partner_sol_reward = match partner.as_mut() {
+    Some(partner) => partner.repay_sol_debt(partner_reward),
+    None => 0,
+  };
+  for ancestor in ancestors.iter_mut() {
+    ancestor.reward = ancestor.partner.repay_sol_debt(ancestor.reward);
   }
+  let to_amount = get_bank_amount(amount, partner_sol_reward, &ancestors)?;
 
   let instruction = &transfer(&payer.key(), &bank_info.key(), to_amount);
   invoke(instruction, to_account_infos).unwrap();
 
   if partner_sol_reward > 0 {
-    let instruction = &transfer(&payer.key(), &partner.key(), partner_sol_reward);
+    let partner_key = match escrow.as_ref() {
+      Some(escrow) => escrow.key(),
+      None => partner.as_ref().unwrap().key(),
+    };
+    let instruction = &transfer(&payer.key(), &partner_key, partner_sol_reward);
     invoke(instruction, to_account_infos).unwrap();
   }
 
+  for ancestor in ancestors.iter() {
+    let ancestor_info = match escrow.as_ref() {
+      Some(escrow) => escrow.to_account_info(),
+      None => ancestor.partner.to_account_info(),
+    };
+    let instruction = &transfer(&payer.key(), &ancestor_info.key(), ancestor.reward);
+    invoke(instruction, &[payer.to_account_info(), ancestor_info, system_program.to_account_info()]).unwrap();
+  }
+
   token_amount += bonus;
 
   // Updating sale_handler details
@@ -142,13 +274,45 @@ pub fn purchase_with_sol(
 
   // Updating purchaser details
   purchaser.set_purchased(token_amount).unwrap();
+  record_purchase(ctx.accounts.purchase_record.as_mut(), payer.key(), Pubkey::default(), partner_token_reward, escrow.is_some(), rewards)?;
+  purchaser.set_purchases().unwrap();
 
   // Updating partner details
-  if !partner_code.is_empty() {
-    partner.set_sol_reward(partner_sol_reward).unwrap();
+  let clock: Clock = Clock::get()?;
+  if let Some(partner) = partner {
+    match escrow.as_mut() {
+      Some(escrow) => {
+        partner.set_deferred_sol_reward(partner_sol_reward, clock.unix_timestamp).unwrap();
+        escrow.set_sol_amount(partner_sol_reward).unwrap();
+      },
+      None => partner.set_sol_reward(partner_sol_reward).unwrap(),
+    };
     partner.set_token_reward(partner_token_reward).unwrap();
+    partner.set_referred_purchase().unwrap();
+    // Only commissioned purchases count towards volume and tiers
+    if partner.is_enabled() && !partner.is_self_referral(payer.key()) {
+      partner.set_sol_volume(usd_amount).unwrap();
+      update_partner_tier(sale_handler, &partner_code, partner, usd_amount)?;
+    }
   };
 
+  for ancestor in ancestors.iter_mut() {
+    match escrow.as_mut() {
+      Some(escrow) => {
+        ancestor.partner.set_deferred_sol_reward(ancestor.reward, clock.unix_timestamp).unwrap();
+        escrow.set_sol_amount(ancestor.reward).unwrap();
+      },
+      None => ancestor.partner.set_sol_reward(ancestor.reward).unwrap(),
+    };
+    ancestor.partner.exit(&crate::ID)?;
+
+    emit!(events::ReferralCommission {
+      partner: ancestor.partner.key(),
+      level: ancestor.level,
+      amount: ancestor.reward,
+    });
+  }
+
   emit!(events::PurchaseWithSol {
     step: step.get_id(),
     purchaser: payer.key(),
@@ -156,25 +320,38 @@ pub fn purchase_with_sol(
     usd_equivalent: usd_amount,
     sol_amount: amount,
     token_amount: token_amount,
+    promo_code: promo_code,
+    promo_bonus: promo_bonus,
   });
   Ok(())
 }
 
-pub fn purchase_with_usdc(
-  ctx: Context<PurchaseUSDC>,
+/// Purchase with any enabled accepted token, `remaining_accounts` carry the referral levels above the direct partner:
+/// partner and partner reward accounts, plus the partner PDA token account when commissions are paid at purchase
+pub fn purchase_with_token<'info>(
+  ctx: Context<'_, '_, 'info, 'info, PurchaseToken<'info>>,
   partner_code: String,
   amount: u64,
+  promo_code: String,
 ) -> Result<()> {
   let payer = &mut ctx.accounts.payer;
   let sale_handler = &mut ctx.accounts.sale_handler;
   let step = &mut ctx.accounts.step;
   let purchaser = &mut ctx.accounts.purchaser;
-  let partner = &mut ctx.accounts.partner;
+  // A bound buyer keeps its partner whatever code comes with the purchase
+  let partner_code = purchaser.get_referral_code(&partner_code).to_string();
+  let mut partner = get_partner(sale_handler, purchaser, &partner_code, &mut ctx.accounts.partner)?;
+  bind_partner(purchaser, partner.as_deref_mut(), &partner_code, payer.key())?;
 
+  let accepted_token = &ctx.accounts.accepted_token;
   let purchaser_ata = &ctx.accounts.purchaser_ata;
   let bank_ata = &ctx.accounts.bank_ata;
   let partner_pda_ata = &ctx.accounts.partner_pda_ata;
+  let escrow_ata = &ctx.accounts.escrow_ata;
+  let mint = &ctx.accounts.mint;
   let token_program = &ctx.accounts.token_program;
+  let associated_token_program = &ctx.accounts.associated_token_program;
+  let system_program = &ctx.accounts.system_program;
 
   if !sale_handler.is_enabled() {
     return err!(errors::SaleHandler::SaleHandlerNotEnabled);
@@ -188,9 +365,47 @@ pub fn purchase_with_usdc(
     return err!(errors::SaleHandler::InactiveStep);
   }
 
-  let usd_amount = u128::from(amount) * 10u128.pow(STABLE_PRECISION);
+  if !accepted_token.is_enabled() {
+    return err!(errors::SaleHandler::AcceptedTokenNotEnabled);
+  }
+
+  let (price, expo) = get_token_price(sale_handler, accepted_token, ctx.accounts.price_update.as_ref())?;
+  // Commissions are split off the paid amount, each transfer then bears its own mint transfer fee
+  let reward_usd = |reward: u64| Ok(get_mint_usd_amount(mint, get_received_amount(mint, reward)?, price, expo));
+  let (partner_reward, secondary_interest) = match partner.as_mut() {
+    Some(partner) => get_interest(sale_handler, &partner_code, partner, payer.key(), amount, &reward_usd)?,
+    None => (0, 0),
+  };
+  let escrow = get_escrow(sale_handler, &mut ctx.accounts.escrow)?;
+  let accounts_per_level = if escrow.is_some() { 2 } else { 3 };
+  let mut ancestors = get_ancestors(sale_handler, partner.as_deref(), payer.key(), ctx.remaining_accounts, accounts_per_level, amount, &reward_usd)?;
+  let mut rewards = get_purchase_rewards(partner.as_deref(), partner_reward, &ancestors);
+  // Clawback debt is paid off first, that part stays with the bank
+  let partner_reward = match (partner.is_some(), ctx.accounts.partner_reward.as_mut()) {
+    (true, Some(reward_account)) => reward_account.repay_debt(partner_reward),
+    (true, None) => return err!(errors::SaleHandler::PartnerRewardMissing),
+    (false, _) => 0,
+  };
+  let mut ancestor_rewards = Vec::new();
+  for ancestor in ancestors.iter_mut() {
+    let mut ancestor_reward = get_partner_reward(&ancestor.accounts[1], ancestor.partner.key(), mint.key(), payer, system_program)?;
+    ancestor.reward = ancestor_reward.repay_debt(ancestor.reward);
+    ancestor_rewards.push(ancestor_reward);
+  }
+  let to_amount = get_bank_amount(amount, partner_reward, &ancestors)?;
+
+  // Only what arrives after the transfer fees is credited
+  let partner_received = get_received_amount(mint, partner_reward)?;
+  let mut received_amount = get_received_amount(mint, to_amount)? + partner_received;
+  for ancestor in ancestors.iter() {
+    received_amount += get_received_amount(mint, ancestor.reward)?;
+  }
+
+  let usd_amount = get_mint_usd_amount(mint, received_amount, price, expo);
   let mut token_amount = usd_amount * 10u128.pow(PRECISION) / u128::from(step.get_price());
-  let bonus = sale_handler.calculate_bonus(usd_amount, token_amount);
+  let promo_bonus = redeem_promo_code(&promo_code, ctx.accounts.promo.as_mut(), ctx.accounts.promo_redemption.as_mut(), step.get_id(), token_amount)?;
+  let bonus = sale_handler.calculate_bonus(usd_amount, token_amount) + get_buyer_bonus(sale_handler, partner.as_deref(), payer.key(), token_amount) + promo_bonus;
+  let partner_token_reward = token_amount * u128::from(secondary_interest) / 10u128.pow(PRECISION);
 
   if sale_handler.get_max_cap() < usd_amount {
     return err!(errors::SaleHandler::SaleHandlerMaxCapExceeded);
@@ -200,32 +415,61 @@ pub fn purchase_with_usdc(
     return err!(errors::SaleHandler::SaleHandlerMinCapNotReached);
   }
 
-  if step.get_total_sold() + token_amount + bonus > step.get_total_supply() {
-    return err!(errors::SaleHandler::StepSupplyExceeded);
+  if accepted_token.get_max_cap() < usd_amount {
+    return err!(errors::SaleHandler::AcceptedTokenMaxCapExceeded);
   }
 
-  let (partner_usdc_reward, partner_token_reward) = get_interest(sale_handler, &partner_code, partner, amount, token_amount).unwrap();
-  let mut to_amount = amount;
-  if partner_usdc_reward > 0 {
-    to_amount = to_amount - partner_usdc_reward;
+  if accepted_token.get_min_cap() > usd_amount {
+    return err!(errors::SaleHandler::AcceptedTokenMinCapNotReached);
   }
 
-  let cpi_accounts = SplTransfer {
+  if step.get_total_sold() + token_amount + bonus > step.get_total_supply() {
+    return err!(errors::SaleHandler::StepSupplyExceeded);
+  }
+
+  let cpi_accounts = TransferChecked {
     from: purchaser_ata.to_account_info(),
+    mint: mint.to_account_info(),
     to: bank_ata.to_account_info(),
     authority: payer.to_account_info(),
   };
   let cpi_program = token_program.to_account_info();
-  token::transfer(CpiContext::new(cpi_program, cpi_accounts), to_amount).unwrap();
-  
-  if partner_usdc_reward > 0 {
-    let cpi_accounts = SplTransfer {
+  token_interface::transfer_checked(CpiContext::new(cpi_program, cpi_accounts), to_amount, mint.decimals).unwrap();
+
+  if partner_reward > 0 {
+    let partner_pda_ata = get_commission_ata(escrow.is_some(), escrow_ata, partner_pda_ata)?;
+
+    let cpi_accounts = TransferChecked {
+      from: purchaser_ata.to_account_info(),
+      mint: mint.to_account_info(),
+      to: partner_pda_ata,
+      authority: payer.to_account_info(),
+    };
+    let cpi_program = token_program.to_account_info();
+    token_interface::transfer_checked(CpiContext::new(cpi_program, cpi_accounts), partner_reward, mint.decimals).unwrap();
+  }
+
+  for ancestor in ancestors.iter() {
+    if ancestor.reward == 0 {
+      continue;
+    }
+
+    let ancestor_ata = match escrow.as_ref() {
+      Some(_) => get_commission_ata(true, escrow_ata, partner_pda_ata)?,
+      None => {
+        init_partner_ata(&ancestor.accounts[2], &ancestor.accounts[0], mint, payer, token_program, associated_token_program, system_program)?;
+        ancestor.accounts[2].clone()
+      },
+    };
+
+    let cpi_accounts = TransferChecked {
       from: purchaser_ata.to_account_info(),
-      to: partner_pda_ata.to_account_info(),
+      mint: mint.to_account_info(),
+      to: ancestor_ata,
       authority: payer.to_account_info(),
     };
     let cpi_program = token_program.to_account_info();
-    token::transfer(CpiContext::new(cpi_program, cpi_accounts), partner_usdc_reward).unwrap();
+    token_interface::transfer_checked(CpiContext::new(cpi_program, cpi_accounts), ancestor.reward, mint.decimals).unwrap();
   }
 
   token_amount += bonus;
@@ -240,163 +484,561 @@ pub fn purchase_with_usdc(
   purchaser.set_purchased(token_amount).unwrap();
 
   // Updating partner details
-  if !partner_code.is_empty() {
-    partner.set_usdc_reward(partner_usdc_reward).unwrap();
+  let clock: Clock = Clock::get()?;
+  if let Some(partner) = partner {
+    let reward = match ctx.accounts.partner_reward.as_mut() {
+      Some(reward) => reward,
+      None => return err!(errors::SaleHandler::PartnerRewardMissing),
+    };
+    // Transfer fees never reach the partner and are left out of clawbacks
+    rewards[0].2 -= partner_reward - partner_received;
+
+    match escrow {
+      Some(_) => reward.set_deferred_reward(partner_received, clock.unix_timestamp).unwrap(),
+      None => reward.set_reward(partner_received).unwrap(),
+    };
     partner.set_token_reward(partner_token_reward).unwrap();
+    partner.set_referred_purchase().unwrap();
+    // Only commissioned purchases count towards volume and tiers
+    if partner.is_enabled() && !partner.is_self_referral(payer.key()) {
+      partner.set_spl_volume(usd_amount).unwrap();
+      reward.set_volume(usd_amount).unwrap();
+      update_partner_tier(sale_handler, &partner_code, partner, usd_amount)?;
+    }
   };
 
-  emit!(events::PurchaseWithUsdc {
+  for (idx, (ancestor, ancestor_reward)) in ancestors.iter_mut().zip(ancestor_rewards.iter_mut()).enumerate() {
+    let ancestor_received = get_received_amount(mint, ancestor.reward)?;
+    rewards[idx + 1].2 -= ancestor.reward - ancestor_received;
+    match escrow {
+      Some(_) => ancestor_reward.set_deferred_reward(ancestor_received, clock.unix_timestamp).unwrap(),
+      None => ancestor_reward.set_reward(ancestor_received).unwrap(),
+    };
+    ancestor_reward.exit(&crate::ID)?;
+    ancestor.partner.exit(&crate::ID)?;
+
+    emit!(events::ReferralCommission {
+      partner: ancestor.partner.key(),
+      level: ancestor.level,
+      amount: ancestor.reward,
+    });
+  }
+
+  record_purchase(ctx.accounts.purchase_record.as_mut(), payer.key(), mint.key(), partner_token_reward, escrow.is_some(), rewards)?;
+  purchaser.set_purchases().unwrap();
+
+  emit!(events::PurchaseWithToken {
     step: step.get_id(),
     purchaser: payer.key(),
     partner: partner_code,
+    mint: accepted_token.get_mint(),
     usd_equivalent: usd_amount,
-    usdc_amount: amount,
+    amount: amount,
+    rate: price * 10u128.pow(PRECISION) / 10u128.pow(expo),
     token_amount: token_amount,
+    promo_code: promo_code,
+    promo_bonus: promo_bonus,
   });
 
   Ok(())
 }
 
-pub fn purchase_with_usdt(
-  ctx: Context<PurchaseUSDT>,
-  partner_code: String,
+pub fn get_price(price_update: &Account<PriceUpdateV2>)
+  -> Result<(u128, u32)>
+{
+  let feed_id = &get_feed_id_from_hex(FEED_ID)?;
+  get_feed_price(price_update, feed_id)
+}
+
+pub fn get_feed_price(price_update: &Account<PriceUpdateV2>, feed_id: &[u8; 32])
+  -> Result<(u128, u32)>
+{
+  let current_price = price_update.get_price_no_older_than(
+      &Clock::get()?,
+      FEED_MAXIMUM_AGE,
+      feed_id,
+  ).unwrap();
+  let price = u64::try_from(current_price.price).unwrap();
+  let expo = u32::try_from(-current_price.exponent).unwrap();
+  Ok((u128::from(price), expo))
+}
+
+/// Price of an accepted token as `price / 10^expo`, from its Pyth feed or its peg guarded by that feed
+pub fn get_token_price(
+  sale_handler: &SaleHandler,
+  accepted_token: &AcceptedToken,
+  price_update: Option<&Account<PriceUpdateV2>>,
+) -> Result<(u128, u32)> {
+  let feed_price = match price_update {
+    _ if !accepted_token.has_price_feed() => None,
+    _ if accepted_token.is_pegged() && !sale_handler.is_depeg_guard_enabled() => None,
+    Some(price_update) if price_update.key() == accepted_token.get_price_update() => Some(get_feed_price(price_update, &accepted_token.get_feed_id())?),
+    _ => return err!(errors::SaleHandler::WrongPriceFeedId),
+  };
+
+  if !accepted_token.is_pegged() {
+    return Ok(feed_price.unwrap());
+  }
+
+  let peg = u128::from(accepted_token.get_fixed_price());
+  let rate = match feed_price {
+    Some((price, expo)) => apply_depeg_guard(peg, price * 10u128.pow(PRECISION) / 10u128.pow(expo), sale_handler.get_depeg_threshold(), sale_handler.get_depeg_limit())?,
+    None => peg,
+  };
+
+  Ok((rate, PRECISION))
+}
+
+/// Keeps `peg` while the oracle `rate` stays within `threshold` of it, relative and in `PRECISION` decimals,
+/// follows the oracle up to `limit` and rejects the purchase beyond, 0 limit disables the guard
+pub fn apply_depeg_guard(
+  peg: u128,
+  rate: u128,
+  threshold: u64,
+  limit: u64,
+) -> Result<u128> {
+  if limit == 0 {
+    return Ok(peg);
+  }
+
+  let deviation = rate.abs_diff(peg) * 10u128.pow(PRECISION) / peg;
+
+  if deviation > u128::from(limit) {
+    return err!(errors::SaleHandler::StablecoinDepegged);
+  }
+
+  if deviation > u128::from(threshold) {
+    return Ok(rate);
+  }
+
+  Ok(peg)
+}
+
+/// USD value in `PRECISION` decimals of `amount` base units of `mint`, priced at `price / 10^expo`
+pub fn get_mint_usd_amount(
+  mint: &InterfaceAccount<Mint>,
   amount: u64,
-) -> Result<()> {
-  let payer = &mut ctx.accounts.payer;
-  let sale_handler = &mut ctx.accounts.sale_handler;
-  let step = &mut ctx.accounts.step;
-  let purchaser = &mut ctx.accounts.purchaser;
-  let partner = &mut ctx.accounts.partner;
+  price: u128,
+  expo: u32,
+) -> u128 {
+  u128::from(amount) * price * 10u128.pow(PRECISION) / 10u128.pow(expo + u32::from(mint.decimals))
+}
 
-  let purchaser_ata = &ctx.accounts.purchaser_ata;
-  let bank_ata = &ctx.accounts.bank_ata;
-  let partner_pda_ata = &ctx.accounts.partner_pda_ata;
-  let token_program = &ctx.accounts.token_program;
+pub fn get_price_test(_price_update: &AccountInfo)
+  -> Result<(u128, u32)>
+{
+  Ok((144000000000, 9))
+}
 
-  if !sale_handler.is_enabled() {
-    return err!(errors::SaleHandler::SaleHandlerNotEnabled);
+/// Empty code means no referral; any other code must belong to a partner registered via `init_partner`
+pub fn get_partner<'a, 'info>(
+  sale_handler: &SaleHandler,
+  purchaser: &Purchaser,
+  partner_code: &str,
+  partner: &'a mut Option<Account<'info, Partner>>,
+) -> Result<Option<&'a mut Account<'info, Partner>>> {
+  if partner_code.is_empty() {
+    return Ok(None);
   }
 
-  if !step.is_enabled() {
-    return err!(errors::SaleHandler::StepNotEnabled);
+  let partner = match partner {
+    Some(partner) if partner.is_pending() => return err!(errors::SaleHandler::PartnerPendingApproval),
+    Some(partner) if partner.is_registered() => partner,
+    _ => return err!(errors::SaleHandler::PartnerNotRegistered),
+  };
+
+  // Buyers bound to a partner disabled later keep purchasing, only without commission
+  if !partner.is_enabled() && !purchaser.is_partner_bound() && sale_handler.get_disabled_partner_purchase_policy() == DisabledPartnerPurchasePolicy::Reject {
+    return err!(errors::SaleHandler::PartnerNotEnabled);
   }
 
-  if sale_handler.get_step() != step.get_id() {
-    return err!(errors::SaleHandler::InactiveStep);
+  Ok(Some(partner))
+}
+
+/// The first purchase referred by an enabled partner binds the buyer to it, only an admin can change it later
+pub fn bind_partner(
+  purchaser: &mut Account<Purchaser>,
+  partner: Option<&mut Account<Partner>>,
+  partner_code: &str,
+  payer: Pubkey,
+) -> Result<()> {
+  if purchaser.is_partner_bound() {
+    return Ok(());
   }
 
-  let usd_amount = u128::from(amount) * 10u128.pow(STABLE_PRECISION);
-  let mut token_amount = usd_amount * 10u128.pow (PRECISION) / u128::from(step.get_price());
-  let bonus = sale_handler.calculate_bonus(usd_amount, token_amount);
+  if let Some(partner) = partner {
+    if !partner.is_enabled() {
+      return Ok(());
+    }
 
-  if sale_handler.get_max_cap() < usd_amount {
-    return err!(errors::SaleHandler::SaleHandlerMaxCapExceeded);
+    purchaser.set_partner_code(partner_code.to_string()).unwrap();
+    partner.set_referred_buyer().unwrap();
+
+    emit!(events::PurchaserPartnerBound {
+      purchaser: payer,
+      partner: partner_code.to_string(),
+    });
   }
 
-  if sale_handler.get_min_cap() > usd_amount {
-    return err!(errors::SaleHandler::SaleHandlerMinCapNotReached);
+  Ok(())
+}
+
+/// Token account receiving commissions: the escrow one in deferred settlement, the partner PDA one otherwise
+fn get_commission_ata<'info>(
+  deferred: bool,
+  escrow_ata: &Option<InterfaceAccount<'info, TokenAccount>>,
+  partner_pda_ata: &Option<InterfaceAccount<'info, TokenAccount>>,
+) -> Result<AccountInfo<'info>> {
+  match (deferred, escrow_ata, partner_pda_ata) {
+    (true, Some(escrow_ata), _) => Ok(escrow_ata.to_account_info()),
+    (true, None, _) => err!(errors::SaleHandler::EscrowMissing),
+    (false, _, Some(partner_pda_ata)) => Ok(partner_pda_ata.to_account_info()),
+    (false, _, None) => err!(errors::SaleHandler::PartnerTokenAccountMissing),
   }
+}
 
-  if step.get_total_sold() + token_amount + bonus > step.get_total_supply() {
-    return err!(errors::SaleHandler::StepSupplyExceeded);
+/// Commission account of `partner` in `mint`, created on its first commission
+fn get_partner_reward<'info>(
+  info: &'info AccountInfo<'info>,
+  partner: Pubkey,
+  mint: Pubkey,
+  payer: &Signer<'info>,
+  system_program: &Program<'info, System>,
+) -> Result<Account<'info, PartnerTokenReward>> {
+  let (partner_reward_key, bump) = Pubkey::find_program_address(&[PARTNER_REWARD_TAG, b"_", partner.as_ref(), mint.as_ref()], &crate::ID);
+  if info.key() != partner_reward_key {
+    return err!(errors::SaleHandler::ReferralAccountsMismatch);
   }
 
-  let (partner_usdt_reward, partner_token_reward) = get_interest(sale_handler, &partner_code, partner, amount, token_amount).unwrap();
-  let mut to_amount = amount;
-  if partner_usdt_reward > 0 {
-    to_amount = to_amount - partner_usdt_reward;
+  if info.owner != &crate::ID {
+    let bump = &[bump];
+    let seeds: &[&[u8]] = &[PARTNER_REWARD_TAG, b"_", partner.as_ref(), mint.as_ref(), bump];
+    let signer_seeds = &[seeds];
+
+    let space = 8 + PartnerTokenReward::MAX_SIZE;
+    let lamports = Rent::get()?.minimum_balance(space).saturating_sub(info.lamports());
+    if lamports > 0 {
+      let instruction = &transfer(&payer.key(), &partner_reward_key, lamports);
+      invoke(instruction, &[payer.to_account_info(), info.clone(), system_program.to_account_info()])?;
+    }
+
+    invoke_signed(&allocate(&partner_reward_key, space as u64), &[info.clone(), system_program.to_account_info()], signer_seeds)?;
+    invoke_signed(&assign(&partner_reward_key, &crate::ID), &[info.clone(), system_program.to_account_info()], signer_seeds)?;
+    info.try_borrow_mut_data()?[..8].copy_from_slice(&PartnerTokenReward::DISCRIMINATOR);
   }
 
-  let cpi_accounts = SplTransfer {
-    from: purchaser_ata.to_account_info(),
-    to: bank_ata.to_account_info(),
-    authority: payer.to_account_info(),
+  Account::try_from(info)
+}
+
+/// Creates the partner PDA token account of an ancestor unless it exists
+fn init_partner_ata<'info>(
+  info: &AccountInfo<'info>,
+  partner: &AccountInfo<'info>,
+  mint: &InterfaceAccount<'info, Mint>,
+  payer: &Signer<'info>,
+  token_program: &Interface<'info, TokenInterface>,
+  associated_token_program: &Program<'info, AssociatedToken>,
+  system_program: &Program<'info, System>,
+) -> Result<()> {
+  if info.key() != get_associated_token_address_with_program_id(&partner.key(), &mint.key(), &token_program.key()) {
+    return err!(errors::SaleHandler::ReferralAccountsMismatch);
+  }
+
+  let cpi_accounts = associated_token::Create {
+    payer: payer.to_account_info(),
+    associated_token: info.clone(),
+    authority: partner.clone(),
+    mint: mint.to_account_info(),
+    system_program: system_program.to_account_info(),
+    token_program: token_program.to_account_info(),
   };
-  let cpi_program = token_program.to_account_info();
-  token::transfer(CpiContext::new(cpi_program, cpi_accounts), to_amount).unwrap();
-  
-  if partner_usdt_reward > 0 {
-    let cpi_accounts = SplTransfer {
-      from: purchaser_ata.to_account_info(),
-      to: partner_pda_ata.to_account_info(),
-      authority: payer.to_account_info(),
+  associated_token::create_idempotent(CpiContext::new(associated_token_program.to_account_info(), cpi_accounts))
+}
+
+pub struct Ancestor<'info> {
+  pub partner: Account<'info, Partner>,
+  // Accounts of the level in `remaining_accounts`, the partner first
+  pub accounts: &'info [AccountInfo<'info>],
+  pub level: u8,
+  pub reward: u64,
+}
+
+/// Walks `parent` links of the direct partner through `remaining_accounts`, `accounts_per_level`
+/// accounts per level starting with the partner account
+pub fn get_ancestors<'info>(
+  sale_handler: &SaleHandler,
+  partner: Option<&Account<'info, Partner>>,
+  payer: Pubkey,
+  remaining_accounts: &'info [AccountInfo<'info>],
+  accounts_per_level: usize,
+  amount: u64,
+  reward_usd: &dyn Fn(u64) -> Result<u128>,
+) -> Result<Vec<Ancestor<'info>>> {
+  let mut ancestors: Vec<Ancestor> = Vec::new();
+  let partner = match partner {
+    Some(partner) if partner.is_enabled() && !partner.is_self_referral(payer) => partner,
+    _ => return Ok(ancestors),
+  };
+
+  let mut accounts = remaining_accounts.chunks(accounts_per_level);
+  let mut parent = partner.get_parent();
+
+  for (idx, interest) in sale_handler.get_referral_interests().iter().enumerate() {
+    if parent == Pubkey::default() {
+      break;
+    }
+
+    let chunk = match accounts.next() {
+      Some(chunk) if chunk.len() == accounts_per_level && chunk[0].key() == parent => chunk,
+      _ => return err!(errors::SaleHandler::ReferralAccountsMismatch),
     };
-    let cpi_program = token_program.to_account_info();
-    token::transfer(CpiContext::new(cpi_program, cpi_accounts), partner_usdt_reward).unwrap();
+
+    if parent == partner.key() || ancestors.iter().any(|ancestor| ancestor.partner.key() == parent) {
+      return err!(errors::SaleHandler::ReferralCycle);
+    }
+
+    let mut ancestor: Account<Partner> = Account::try_from(&chunk[0])?;
+    parent = ancestor.get_parent();
+
+    // Disabled ancestors are skipped, the chain continues above them
+    if !ancestor.is_registered() || !ancestor.is_enabled() {
+      continue;
+    }
+
+    if ancestor.is_self_referral(payer) {
+      if sale_handler.get_self_referral_policy() == SelfReferralPolicy::Reject {
+        return err!(errors::SaleHandler::SelfReferral);
+      }
+
+      emit!(events::AncestorSelfReferralPurchase {
+        partner: ancestor.key(),
+        level: (idx + 1) as u8,
+        purchaser: payer,
+      });
+
+      continue;
+    }
+
+    let mut reward = amount * interest / 10u64.pow(PRECISION);
+    let reward_usd = reward_usd(reward)?;
+    let allowed_usd = cap_commission(&mut ancestor, reward_usd);
+    if allowed_usd < reward_usd {
+      reward = u64::try_from(u128::from(reward) * allowed_usd / reward_usd).unwrap();
+    }
+
+    if reward == 0 {
+      continue;
+    }
+
+    ancestors.push(Ancestor {
+      partner: ancestor,
+      accounts: chunk,
+      level: (idx + 1) as u8,
+      reward: reward,
+    });
   }
 
-  token_amount += bonus;
+  Ok(ancestors)
+}
 
-  // Updating sale_handler details
-  sale_handler.set_total_sold(token_amount).unwrap();
+/// Commissions of a purchase by level before debt repayment, the direct partner first
+fn get_purchase_rewards(
+  partner: Option<&Account<Partner>>,
+  partner_reward: u64,
+  ancestors: &[Ancestor],
+) -> Vec<(Pubkey, u8, u64)> {
+  let mut rewards = Vec::new();
+  if let Some(partner) = partner {
+    rewards.push((partner.key(), 0, partner_reward));
+  }
 
-  // Updating step details
-  step.set_total_sold(token_amount).unwrap();
+  for ancestor in ancestors.iter() {
+    rewards.push((ancestor.partner.key(), ancestor.level, ancestor.reward));
+  }
 
-  // Updating purchaser details
-  purchaser.set_purchased(token_amount).unwrap();
+  rewards
+}
 
-  // Updating partner details
-  if !partner_code.is_empty() {
-    partner.set_usdt_reward(partner_usdt_reward).unwrap();
-    partner.set_token_reward(partner_token_reward).unwrap();
+/// Keeps the commissions of a referred purchase for clawback, the record is required once any commission is paid
+fn record_purchase(
+  purchase_record: Option<&mut Account<PurchaseRecord>>,
+  purchaser: Pubkey,
+  mint: Pubkey,
+  token_reward: u128,
+  deferred: bool,
+  rewards: Vec<(Pubkey, u8, u64)>,
+) -> Result<()> {
+  let paid = token_reward > 0 || rewards.iter().any(|(_, _, reward)| *reward > 0);
+  let purchase_record = match purchase_record {
+    Some(purchase_record) => purchase_record,
+    None if !paid => return Ok(()),
+    None => return err!(errors::SaleHandler::PurchaseRecordMissing),
   };
 
-  emit!(events::PurchaseWithUsdt {
-    step: step.get_id(),
-    purchaser: payer.key(),
-    partner: partner_code,
-    usd_equivalent: usd_amount,
-    usdt_amount: amount,
-    token_amount: token_amount,
-  });
+  purchase_record.init(purchaser, mint, token_reward, deferred).unwrap();
+  for (partner, level, reward) in rewards {
+    // The direct partner stays first, it carries the token reward
+    if level > 0 && reward == 0 {
+      continue;
+    }
+
+    purchase_record.add_reward(partner, level, reward).unwrap();
+  }
 
   Ok(())
 }
 
-pub fn get_price(price_update: &Account<PriceUpdateV2>)
-  -> Result<(u128, u32)>
-{
-  let feed_id = &get_feed_id_from_hex(FEED_ID)?;
-  let current_price = price_update.get_price_no_older_than(
-      &Clock::get()?,
-      FEED_MAXIMUM_AGE,
-      feed_id,
-  ).unwrap();
-  let price = u64::try_from(current_price.price).unwrap();
-  let expo = u32::try_from(-current_price.exponent).unwrap();
-  Ok((u128::from(price), expo))
+/// Part of the payment left for the bank once every commission is taken out
+pub fn get_bank_amount(
+  amount: u64,
+  partner_reward: u64,
+  ancestors: &Vec<Ancestor>,
+) -> Result<u64> {
+  let commission = ancestors.iter().fold(partner_reward, |total, ancestor| total + ancestor.reward);
+  match amount.checked_sub(commission) {
+    Some(to_amount) => Ok(to_amount),
+    None => err!(errors::SaleHandler::PartnerCommissionTooLarge),
+  }
 }
 
-pub fn get_price_test(_price_update: &AccountInfo)
-  -> Result<(u128, u32)>
-{
-  Ok((144000000000, 9))
+/// Amount arriving at the destination once a Token-2022 transfer fee is withheld
+pub fn get_received_amount(
+  mint: &InterfaceAccount<Mint>,
+  amount: u64,
+) -> Result<u64> {
+  let fee_config = match get_mint_extension_data::<TransferFeeConfig>(&mint.to_account_info()) {
+    Ok(fee_config) => fee_config,
+    Err(_) => return Ok(amount),
+  };
+
+  let clock: Clock = Clock::get()?;
+  let fee = fee_config.calculate_epoch_fee(clock.epoch, amount).unwrap();
+
+  Ok(amount - fee)
+}
+
+/// Adds the referred volume and moves the partner to the tier it now qualifies for
+pub fn update_partner_tier(
+  sale_handler: &SaleHandler,
+  partner_code: &str,
+  partner: &mut Account<Partner>,
+  usd_amount: u128,
+) -> Result<()> {
+  partner.set_referred_volume(usd_amount).unwrap();
+
+  let previous_tier = partner.get_tier();
+  let tier = sale_handler.calculate_tier(partner.get_referred_volume());
+  if tier != previous_tier {
+    partner.set_tier(tier).unwrap();
+
+    emit!(events::PartnerTierChanged {
+      partner: partner_code.to_string(),
+      previous_tier: previous_tier,
+      tier: tier,
+      referred_volume: partner.get_referred_volume(),
+    });
+  }
+
+  Ok(())
+}
+
+/// Largest of the sale-wide default, the partner override and the partner tier
+pub fn get_partner_interest(
+  sale_handler: &SaleHandler,
+  partner: &Partner,
+) -> (u64, u64) {
+  let (sale_handler_main_interest, sale_handler_secondary_interest) = sale_handler.get_interest();
+  let (partner_main_interest, partner_secondary_interest) = partner.get_interest();
+  let (tier_main_interest, tier_secondary_interest) = sale_handler.get_tier_interest(partner.get_tier());
+
+  let main_interest = u64::max(u64::max(sale_handler_main_interest, partner_main_interest), tier_main_interest);
+  let secondary_interest = u64::max(u64::max(sale_handler_secondary_interest, partner_secondary_interest), tier_secondary_interest);
+
+  (main_interest, secondary_interest)
 }
 
+/// Buyer bonus of the partner code, limited to what the referral budget leaves after the partner commission
+pub fn get_buyer_bonus(
+  sale_handler: &SaleHandler,
+  partner: Option<&Account<Partner>>,
+  payer: Pubkey,
+  token_amount: u128,
+) -> u128 {
+  let partner = match partner {
+    Some(partner) if partner.is_enabled() && !partner.is_self_referral(payer) => partner,
+    _ => return 0,
+  };
+
+  let (main_interest, _) = get_partner_interest(sale_handler, partner);
+  let referral_interests = main_interest + sale_handler.get_referral_interests_total();
+  let available = sale_handler.get_referral_budget().saturating_sub(referral_interests);
+  let buyer_bonus = u64::min(partner.get_buyer_bonus(), available);
+
+  token_amount * u128::from(buyer_bonus) / 10u128.pow(PRECISION)
+}
+
+/// Commission on `amount` and the secondary interest on purchased tokens, both scaled down to the partner cap;
+/// `reward_usd` values a commission as it arrives at its destination
 pub fn get_interest(
   sale_handler: &mut Account<SaleHandler>,
   partner_code: &str,
   partner: &mut Account<Partner>,
+  payer: Pubkey,
   amount: u64,
-  token_amount: u128,
+  reward_usd: &dyn Fn(u64) -> Result<u128>,
 )
-  -> Result<(u64, u128)>
+  -> Result<(u64, u64)>
 {
   if partner_code.is_empty() {
     return Ok((0, 0));
   };
 
-  let (sale_handler_main_interest, sale_handler_secondary_interest) = sale_handler.get_interest();
-  let (partner_main_interest, partner_secondary_interest) = partner.get_interest();
+  if !partner.is_enabled() {
+    emit!(events::DisabledPartnerPurchase {
+      partner: partner_code.to_string(),
+    });
+
+    return Ok((0, 0));
+  }
+
+  if partner.is_self_referral(payer) {
+    if sale_handler.get_self_referral_policy() == SelfReferralPolicy::Reject {
+      return err!(errors::SaleHandler::SelfReferral);
+    }
+
+    emit!(events::SelfReferralPurchase {
+      partner: partner_code.to_string(),
+      purchaser: payer,
+    });
+
+    return Ok((0, 0));
+  }
+
+  let (main_interest, secondary_interest) = get_partner_interest(sale_handler, partner);
 
-  let main_interest = u64::max(sale_handler_main_interest, partner_main_interest);
-  let secondary_interest = u64::max(sale_handler_secondary_interest, partner_secondary_interest);
+  let reward = amount * main_interest / 10u64.pow(PRECISION);
 
-  let amount = amount * main_interest / 10u64.pow(PRECISION);
-  let reward_token_amount = token_amount * u128::from(secondary_interest) / 10u128.pow(PRECISION);
+  let reward_usd = reward_usd(reward)?;
+  let allowed_usd = cap_commission(partner, reward_usd);
+  if allowed_usd < reward_usd {
+    let reward = u64::try_from(u128::from(reward) * allowed_usd / reward_usd).unwrap();
+    let secondary_interest = u64::try_from(u128::from(secondary_interest) * allowed_usd / reward_usd).unwrap();
 
-  Ok((amount, reward_token_amount))
+    return Ok((reward, secondary_interest));
+  }
+
+  Ok((reward, secondary_interest))
+}
+
+/// Commission beyond the partner cap is not paid, returns the USD value left to pay out of `reward_usd`
+fn cap_commission(
+  partner: &mut Partner,
+  reward_usd: u128,
+) -> u128 {
+  let allowed_usd = partner.get_available_commission(reward_usd);
+  partner.set_commission_usd(allowed_usd).unwrap();
+
+  allowed_usd
 }
 
 #[derive(Accounts)]
@@ -441,6 +1083,96 @@ pub struct SetSaleHandlerBonus<'info> {
   pub payer: Signer<'info>,
 }
 
+#[derive(Accounts)]
+#[instruction(token_mint: Pubkey, tge_timestamp: i64, tge_percent: u64, vesting_period: i64)]
+pub struct SetSaleHandlerTge<'info> {
+  #[account(mut)]
+  pub sale_handler: Account<'info, SaleHandler>,
+  #[account(mut)]
+  pub payer: Signer<'info>,
+}
+
+#[derive(Accounts)]
+#[instruction(purchase_policy: DisabledPartnerPurchasePolicy, claim_policy: DisabledPartnerClaimPolicy)]
+pub struct SetSaleHandlerPartnerPolicy<'info> {
+  #[account(mut)]
+  pub sale_handler: Account<'info, SaleHandler>,
+  #[account(mut)]
+  pub payer: Signer<'info>,
+}
+
+#[derive(Accounts)]
+#[instruction(partner_registration: PartnerRegistration)]
+pub struct SetSaleHandlerPartnerRegistration<'info> {
+  #[account(mut)]
+  pub sale_handler: Account<'info, SaleHandler>,
+  #[account(mut)]
+  pub payer: Signer<'info>,
+}
+
+#[derive(Accounts)]
+#[instruction(interests: Vec<u64>)]
+pub struct SetSaleHandlerReferralInterests<'info> {
+  #[account(mut)]
+  pub sale_handler: Account<'info, SaleHandler>,
+  #[account(mut)]
+  pub payer: Signer<'info>,
+}
+
+#[derive(Accounts)]
+#[instruction(thresholds: Vec<u64>, main_interests: Vec<u64>, secondary_interests: Vec<u64>)]
+pub struct SetSaleHandlerPartnerTiers<'info> {
+  #[account(mut)]
+  pub sale_handler: Account<'info, SaleHandler>,
+  #[account(mut)]
+  pub payer: Signer<'info>,
+}
+
+#[derive(Accounts)]
+#[instruction(referral_budget: u64)]
+pub struct SetSaleHandlerReferralBudget<'info> {
+  #[account(mut)]
+  pub sale_handler: Account<'info, SaleHandler>,
+  #[account(mut)]
+  pub payer: Signer<'info>,
+}
+
+#[derive(Accounts)]
+#[instruction(self_referral_policy: SelfReferralPolicy)]
+pub struct SetSaleHandlerSelfReferralPolicy<'info> {
+  #[account(mut)]
+  pub sale_handler: Account<'info, SaleHandler>,
+  #[account(mut)]
+  pub payer: Signer<'info>,
+}
+
+#[derive(Accounts)]
+#[instruction(settlement_mode: SettlementMode, holding_period: i64)]
+pub struct SetSaleHandlerSettlement<'info> {
+  #[account(mut)]
+  pub sale_handler: Account<'info, SaleHandler>,
+  #[account(mut)]
+  pub payer: Signer<'info>,
+}
+
+#[derive(Accounts)]
+#[instruction(claim_period: i64)]
+pub struct SetSaleHandlerClaimPeriod<'info> {
+  #[account(mut)]
+  pub sale_handler: Account<'info, SaleHandler>,
+  #[account(mut)]
+  pub payer: Signer<'info>,
+}
+
+#[derive(Accounts)]
+#[instruction(depeg_threshold: u64, depeg_limit: u64)]
+pub struct SetSaleHandlerDepegGuard<'info> {
+  #[account(mut)]
+  pub sale_handler: Account<'info, SaleHandler>,
+  #[account(mut)]
+  pub payer: Signer<'info>,
+}
+
 #[derive(Accounts)]
 pub struct SetSaleHandlerEnabled<'info> {
   #[account(mut)]
@@ -458,7 +1190,7 @@ pub struct SetSaleHandlerDisabled<'info> {
 }
 
 #[derive(Accounts)]
-#[instruction(partner_code: String, amount: u64)]
+#[instruction(partner_code: String, amount: u64, promo_code: String)]
 pub struct PurchaseSol<'info> {
   #[account(mut)]
   pub sale_handler: Account<'info, SaleHandler>,
@@ -478,18 +1210,58 @@ pub struct PurchaseSol<'info> {
     bump
   )]
   pub purchaser: Account<'info, Purchaser>,
+  #[account(
+    mut,
+    seeds = [
+      PARTNER_TAG,
+      b"_",
+      purchaser.get_referral_code(&partner_code).as_ref()
+    ],
+    bump
+  )]
+  pub partner: Option<Account<'info, Partner>>,
+  #[account(
+    init,
+    payer = payer,
+    space = 8 + PurchaseRecord::MAX_SIZE,
+    seeds = [
+      PURCHASE_RECORD_TAG,
+      b"_",
+      payer.key().as_ref(),
+      purchaser.get_purchases().to_le_bytes().as_ref()
+    ],
+    bump
+  )]
+  pub purchase_record: Option<Account<'info, PurchaseRecord>>,
+  #[account(
+    mut,
+    seeds = [
+      PROMO_CODE_TAG,
+      b"_",
+      promo_code.as_ref()
+    ],
+    bump
+  )]
+  pub promo: Option<Account<'info, PromoCode>>,
   #[account(
     init_if_needed,
     payer = payer,
-    space = 8 + Partner::MAX_SIZE,
+    space = 8 + PromoRedemption::MAX_SIZE,
     seeds = [
-      PARTNER_TAG,
+      PROMO_CODE_TAG,
       b"_",
-      partner_code.as_ref()
+      promo_code.as_ref(),
+      payer.key().as_ref()
     ],
     bump
   )]
-  pub partner: Account<'info, Partner>,
+  pub promo_redemption: Option<Account<'info, PromoRedemption>>,
+  #[account(
+    mut,
+    seeds = [ESCROW_TAG],
+    bump
+  )]
+  pub escrow: Option<Account<'info, Escrow>>,
   /// CHECK: price oracle
   pub price_update: Account<'info, PriceUpdateV2>,
   #[account(mut)]
@@ -499,8 +1271,8 @@ pub struct PurchaseSol<'info> {
 }
 
 #[derive(Accounts)]
-#[instruction(partner_code: String, amount: u64)]
-pub struct PurchaseUSDC<'info> {
+#[instruction(partner_code: String, amount: u64, promo_code: String)]
+pub struct PurchaseToken<'info> {
   #[account(mut)]
   pub sale_handler: Account<'info, SaleHandler>,
   #[account(mut)]
@@ -520,90 +1292,161 @@ pub struct PurchaseUSDC<'info> {
   )]
   pub purchaser: Account<'info, Purchaser>,
   #[account(
-    init_if_needed,
-    payer = payer,
-    space = 8 + Partner::MAX_SIZE,
+    mut,
     seeds = [
       PARTNER_TAG,
       b"_",
-      partner_code.as_ref()
+      purchaser.get_referral_code(&partner_code).as_ref()
     ],
     bump
   )]
-  pub partner: Account<'info, Partner>,
+  pub partner: Option<Account<'info, Partner>>,
   #[account(
-    mut,
-    constraint = purchaser_ata.mint == USDC.parse::<Pubkey>().unwrap(),
-    constraint = purchaser_ata.owner == payer.key(),
+    init,
+    payer = payer,
+    space = 8 + PurchaseRecord::MAX_SIZE,
+    seeds = [
+      PURCHASE_RECORD_TAG,
+      b"_",
+      payer.key().as_ref(),
+      purchaser.get_purchases().to_le_bytes().as_ref()
+    ],
+    bump
   )]
-  pub purchaser_ata: Account<'info, TokenAccount>,
+  pub purchase_record: Option<Account<'info, PurchaseRecord>>,
   #[account(
-    mut,
-    constraint = bank_ata.mint == USDC.parse::<Pubkey>().unwrap(),
-    constraint = bank_ata.owner == BANK.parse::<Pubkey>().unwrap(),
+    init_if_needed,
+    payer = payer,
+    space = 8 + PartnerTokenReward::MAX_SIZE,
+    seeds = [
+      PARTNER_REWARD_TAG,
+      b"_",
+      partner.as_ref().map(|partner| partner.key()).unwrap_or_default().as_ref(),
+      mint.key().as_ref()
+    ],
+    bump,
+    constraint = partner.is_some() @ errors::SaleHandler::PartnerMissing,
   )]
-  pub bank_ata: Account<'info, TokenAccount>,
+  pub partner_reward: Option<Account<'info, PartnerTokenReward>>,
   #[account(
     mut,
-    constraint = partner_pda_ata.mint == USDC.parse::<Pubkey>().unwrap(),
-    constraint = partner_pda_ata.owner == partner.key(),
+    seeds = [
+      PROMO_CODE_TAG,
+      b"_",
+      promo_code.as_ref()
+    ],
+    bump
   )]
-  pub partner_pda_ata: Account<'info, TokenAccount>,
-  pub token_program: Program<'info, Token>,
-  pub system_program: Program<'info, System>,
-}
-
-#[derive(Accounts)]
-#[instruction(partner_code: String, amount: u64)]
-pub struct PurchaseUSDT<'info> {
-  #[account(mut)]
-  pub sale_handler: Account<'info, SaleHandler>,
-  #[account(mut)]
-  pub payer: Signer<'info>,
-  #[account(mut)]
-  pub step: Account<'info, Step>,
+  pub promo: Option<Account<'info, PromoCode>>,
   #[account(
     init_if_needed,
     payer = payer,
-    space = 8 + Purchaser::MAX_SIZE,
+    space = 8 + PromoRedemption::MAX_SIZE,
     seeds = [
-      PURCHASER_TAG,
+      PROMO_CODE_TAG,
       b"_",
+      promo_code.as_ref(),
       payer.key().as_ref()
     ],
     bump
   )]
-  pub purchaser: Account<'info, Purchaser>,
+  pub promo_redemption: Option<Account<'info, PromoRedemption>>,
+  #[account(
+    mut,
+    seeds = [ESCROW_TAG],
+    bump
+  )]
+  pub escrow: Option<Account<'info, Escrow>>,
   #[account(
-    init_if_needed,
-    payer = payer,
-    space = 8 + Partner::MAX_SIZE,
     seeds = [
-      PARTNER_TAG,
+      ACCEPTED_TOKEN_TAG,
       b"_",
-      partner_code.as_ref()
+      mint.key().as_ref()
     ],
     bump
   )]
-  pub partner: Account<'info, Partner>,
+  pub accepted_token: Account<'info, AcceptedToken>,
+  pub mint: InterfaceAccount<'info, Mint>,
+  pub price_update: Option<Account<'info, PriceUpdateV2>>,
   #[account(
     mut,
-    constraint = purchaser_ata.mint == USDT.parse::<Pubkey>().unwrap(),
+    constraint = purchaser_ata.mint == mint.key(),
     constraint = purchaser_ata.owner == payer.key(),
   )]
-  pub purchaser_ata: Account<'info, TokenAccount>,
+  pub purchaser_ata: InterfaceAccount<'info, TokenAccount>,
+  #[account(address = BANK.parse::<Pubkey>().unwrap())]
+  /// CHECK: bank info
+  pub bank_info: AccountInfo<'info>,
   #[account(
-    mut,
-    constraint = bank_ata.mint == USDT.parse::<Pubkey>().unwrap(),
-    constraint = bank_ata.owner == BANK.parse::<Pubkey>().unwrap(),
+    init_if_needed,
+    payer = payer,
+    associated_token::mint = mint,
+    associated_token::authority = bank_info,
+    associated_token::token_program = token_program,
   )]
-  pub bank_ata: Account<'info, TokenAccount>,
+  pub bank_ata: InterfaceAccount<'info, TokenAccount>,
   #[account(
-    mut,
-    constraint = partner_pda_ata.mint == USDT.parse::<Pubkey>().unwrap(),
-    constraint = partner_pda_ata.owner == partner.key(),
+    init_if_needed,
+    payer = payer,
+    associated_token::mint = mint,
+    associated_token::authority = partner,
+    associated_token::token_program = token_program,
   )]
-  pub partner_pda_ata: Account<'info, TokenAccount>,
-  pub token_program: Program<'info, Token>,
+  pub partner_pda_ata: Option<InterfaceAccount<'info, TokenAccount>>,
+  #[account(
+    init_if_needed,
+    payer = payer,
+    associated_token::mint = mint,
+    associated_token::authority = escrow,
+    associated_token::token_program = token_program,
+  )]
+  pub escrow_ata: Option<InterfaceAccount<'info, TokenAccount>>,
+  pub token_program: Interface<'info, TokenInterface>,
+  pub associated_token_program: Program<'info, AssociatedToken>,
   pub system_program: Program<'info, System>,
 }
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  const PEG: u128 = 1_000_000_000;
+
+  fn depeg_err(rate: u128, threshold: u64, limit: u64) -> Error {
+    match apply_depeg_guard(PEG, rate, threshold, limit) {
+      Ok(_) => panic!("expected an error"),
+      Err(error) => error,
+    }
+  }
+
+  #[test]
+  fn disabled_guard_keeps_peg() {
+    assert_eq!(apply_depeg_guard(PEG, 500_000_000, 0, 0).unwrap(), PEG);
+  }
+
+  #[test]
+  fn keeps_peg_within_threshold() {
+    assert_eq!(apply_depeg_guard(PEG, 995_000_000, 5_000_000, 50_000_000).unwrap(), PEG);
+    assert_eq!(apply_depeg_guard(PEG, 1_005_000_000, 5_000_000, 50_000_000).unwrap(), PEG);
+  }
+
+  #[test]
+  fn follows_rate_beyond_threshold() {
+    assert_eq!(apply_depeg_guard(PEG, 980_000_000, 5_000_000, 50_000_000).unwrap(), 980_000_000);
+    assert_eq!(apply_depeg_guard(PEG, 1_020_000_000, 5_000_000, 50_000_000).unwrap(), 1_020_000_000);
+    assert_eq!(apply_depeg_guard(PEG, 950_000_000, 5_000_000, 50_000_000).unwrap(), 950_000_000);
+  }
+
+  #[test]
+  fn rejects_beyond_limit() {
+    assert_eq!(depeg_err(949_999_999, 5_000_000, 50_000_000), errors::SaleHandler::StablecoinDepegged.into());
+    assert_eq!(depeg_err(1_060_000_000, 5_000_000, 50_000_000), errors::SaleHandler::StablecoinDepegged.into());
+  }
+
+  #[test]
+  fn deviation_is_relative_to_peg() {
+    // 2% off a 0.5 peg
+    assert_eq!(apply_depeg_guard(PEG / 2, 490_000_000, 10_000_000, 50_000_000).unwrap(), 490_000_000);
+    assert_eq!(apply_depeg_guard(PEG / 2, 497_500_000, 10_000_000, 50_000_000).unwrap(), PEG / 2);
+  }
+}
@@ -0,0 +1,77 @@
+use anchor_lang::prelude::*;
+use crate::errors;
+use crate::state::signer_set::SignerSet;
+
+use crate::config::{ SIGNATURE_SIGNER, SIGNER_SET_TAG };
+
+pub fn init_signer_set(
+  ctx: Context<InitSignerSet>,
+) -> Result<()> {
+  let signer_set = &mut ctx.accounts.signer_set;
+  signer_set.init(SIGNATURE_SIGNER.parse::<Pubkey>().unwrap())
+}
+
+pub fn add_signer(
+  ctx: Context<AddSigner>,
+  key: Pubkey,
+  active_from: i64,
+) -> Result<()> {
+  let signer_set = &mut ctx.accounts.signer_set;
+  let clock: Clock = Clock::get()?;
+  signer_set.add_signer(key, active_from, clock.unix_timestamp)
+}
+
+pub fn retire_signer(
+  ctx: Context<RetireSigner>,
+  key: Pubkey,
+  grace_period: i64,
+) -> Result<()> {
+  if grace_period < 0 {
+    return err!(errors::SaleHandler::WrongGracePeriod);
+  }
+
+  let signer_set = &mut ctx.accounts.signer_set;
+  let clock: Clock = Clock::get()?;
+  signer_set.retire_signer(key, clock.unix_timestamp + grace_period)
+}
+
+#[derive(Accounts)]
+pub struct InitSignerSet<'info> {
+  #[account(
+    init,
+    payer = payer,
+    space = 8 + SignerSet::MAX_SIZE,
+    seeds = [SIGNER_SET_TAG],
+    bump,
+  )]
+  pub signer_set: Account<'info, SignerSet>,
+  #[account(mut)]
+  pub payer: Signer<'info>,
+  pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(key: Pubkey, active_from: i64)]
+pub struct AddSigner<'info> {
+  #[account(
+    mut,
+    seeds = [SIGNER_SET_TAG],
+    bump,
+  )]
+  pub signer_set: Account<'info, SignerSet>,
+  #[account(mut)]
+  pub payer: Signer<'info>,
+}
+
+#[derive(Accounts)]
+#[instruction(key: Pubkey, grace_period: i64)]
+pub struct RetireSigner<'info> {
+  #[account(
+    mut,
+    seeds = [SIGNER_SET_TAG],
+    bump,
+  )]
+  pub signer_set: Account<'info, SignerSet>,
+  #[account(mut)]
+  pub payer: Signer<'info>,
+}
@@ -13,7 +13,7 @@ declare_id!("EtWFjjyscJFt29nJp5vJdQmQ3usqY1dKmQnTbbNNPqGY");
 #[program]
 pub mod sale_handler {
 
-use signature::check_sign;
+use signature::{ check_claim, check_invitation, ClaimCurrency, ClaimRequest };
 
   use super::*;
 
@@ -63,6 +63,142 @@ use signature::check_sign;
     instructions::sale_handler::set_sale_handler_purchase_bonus(ctx, thresholds, percents)
   }
 
+  pub fn set_sale_handler_tge(
+    ctx: Context<SetSaleHandlerTge>,
+    token_mint: Pubkey,
+    tge_timestamp: i64,
+    tge_percent: u64,
+    vesting_period: i64,
+  ) -> Result<()> {
+    if !config::only_owners(ctx.accounts.payer.key()) {
+      return err!(errors::SaleHandler::Unauthorized);
+    }
+
+    instructions::sale_handler::set_sale_handler_tge(ctx, token_mint, tge_timestamp, tge_percent, vesting_period)
+  }
+
+  pub fn set_sale_handler_partner_policy(
+    ctx: Context<SetSaleHandlerPartnerPolicy>,
+    purchase_policy: state::sale_handler::DisabledPartnerPurchasePolicy,
+    claim_policy: state::sale_handler::DisabledPartnerClaimPolicy,
+  ) -> Result<()> {
+    if !config::only_owners(ctx.accounts.payer.key()) {
+      return err!(errors::SaleHandler::Unauthorized);
+    }
+
+    instructions::sale_handler::set_sale_handler_partner_policy(ctx, purchase_policy, claim_policy)
+  }
+
+  pub fn set_sale_handler_referral_interests(
+    ctx: Context<SetSaleHandlerReferralInterests>,
+    interests: Vec<u64>,
+  ) -> Result<()> {
+    if !config::only_owners(ctx.accounts.payer.key()) {
+      return err!(errors::SaleHandler::Unauthorized);
+    }
+
+    instructions::sale_handler::set_sale_handler_referral_interests(ctx, interests)
+  }
+
+  pub fn set_sale_handler_partner_tiers(
+    ctx: Context<SetSaleHandlerPartnerTiers>,
+    thresholds: Vec<u64>,
+    main_interests: Vec<u64>,
+    secondary_interests: Vec<u64>,
+  ) -> Result<()> {
+    if !config::only_owners(ctx.accounts.payer.key()) {
+      return err!(errors::SaleHandler::Unauthorized);
+    }
+
+    instructions::sale_handler::set_sale_handler_partner_tiers(ctx, thresholds, main_interests, secondary_interests)
+  }
+
+  pub fn set_sale_handler_referral_budget(
+    ctx: Context<SetSaleHandlerReferralBudget>,
+    referral_budget: u64,
+  ) -> Result<()> {
+    if !config::only_owners(ctx.accounts.payer.key()) {
+      return err!(errors::SaleHandler::Unauthorized);
+    }
+
+    instructions::sale_handler::set_sale_handler_referral_budget(ctx, referral_budget)
+  }
+
+  pub fn set_sale_handler_self_referral_policy(
+    ctx: Context<SetSaleHandlerSelfReferralPolicy>,
+    self_referral_policy: state::sale_handler::SelfReferralPolicy,
+  ) -> Result<()> {
+    if !config::only_owners(ctx.accounts.payer.key()) {
+      return err!(errors::SaleHandler::Unauthorized);
+    }
+
+    instructions::sale_handler::set_sale_handler_self_referral_policy(ctx, self_referral_policy)
+  }
+
+  pub fn set_sale_handler_settlement(
+    ctx: Context<SetSaleHandlerSettlement>,
+    settlement_mode: state::sale_handler::SettlementMode,
+    holding_period: i64,
+  ) -> Result<()> {
+    if !config::only_owners(ctx.accounts.payer.key()) {
+      return err!(errors::SaleHandler::Unauthorized);
+    }
+
+    instructions::sale_handler::set_sale_handler_settlement(ctx, settlement_mode, holding_period)
+  }
+
+  pub fn set_sale_handler_claim_period(
+    ctx: Context<SetSaleHandlerClaimPeriod>,
+    claim_period: i64,
+  ) -> Result<()> {
+    if !config::only_owners(ctx.accounts.payer.key()) {
+      return err!(errors::SaleHandler::Unauthorized);
+    }
+
+    instructions::sale_handler::set_sale_handler_claim_period(ctx, claim_period)
+  }
+
+  pub fn set_sale_handler_depeg_guard(
+    ctx: Context<SetSaleHandlerDepegGuard>,
+    depeg_threshold: u64,
+    depeg_limit: u64,
+  ) -> Result<()> {
+    if !config::only_owners(ctx.accounts.payer.key()) {
+      return err!(errors::SaleHandler::Unauthorized);
+    }
+
+    instructions::sale_handler::set_sale_handler_depeg_guard(ctx, depeg_threshold, depeg_limit)
+  }
+
+  pub fn migrate_sale_handler(
+    ctx: Context<MigrateSaleHandler>,
+  ) -> Result<()> {
+    if !config::only_owners(ctx.accounts.payer.key()) {
+      return err!(errors::SaleHandler::Unauthorized);
+    }
+
+    instructions::migration::migrate_sale_handler(ctx)
+  }
+
+  pub fn migrate_partner(
+    ctx: Context<MigratePartner>,
+    _partner_code: String,
+    owner: Pubkey,
+    registered: bool,
+  ) -> Result<()> {
+    if !config::only_owners(ctx.accounts.payer.key()) {
+      return err!(errors::SaleHandler::Unauthorized);
+    }
+
+    instructions::migration::migrate_partner(ctx, owner, registered)
+  }
+
+  pub fn migrate_purchaser(
+    ctx: Context<MigratePurchaser>,
+  ) -> Result<()> {
+    instructions::migration::migrate_purchaser(ctx)
+  }
+
   pub fn enable_sale_handler(
     ctx: Context<SetSaleHandlerEnabled>,
   ) -> Result<()> {
@@ -83,28 +219,239 @@ use signature::check_sign;
     instructions::sale_handler::disable_sale_handler(ctx)
   }
 
-  pub fn purchase_with_sol(
-    ctx: Context<PurchaseSol>,
+  pub fn init_signer_set(
+    ctx: Context<InitSignerSet>,
+  ) -> Result<()> {
+    if !config::only_owners(ctx.accounts.payer.key()) {
+      return err!(errors::SaleHandler::Unauthorized);
+    }
+
+    instructions::signer_set::init_signer_set(ctx)
+  }
+
+  pub fn add_signer(
+    ctx: Context<AddSigner>,
+    key: Pubkey,
+    active_from: i64,
+  ) -> Result<()> {
+    if !config::only_owners(ctx.accounts.payer.key()) {
+      return err!(errors::SaleHandler::Unauthorized);
+    }
+
+    instructions::signer_set::add_signer(ctx, key, active_from)
+  }
+
+  pub fn retire_signer(
+    ctx: Context<RetireSigner>,
+    key: Pubkey,
+    grace_period: i64,
+  ) -> Result<()> {
+    if !config::only_owners(ctx.accounts.payer.key()) {
+      return err!(errors::SaleHandler::Unauthorized);
+    }
+
+    instructions::signer_set::retire_signer(ctx, key, grace_period)
+  }
+
+  pub fn purchase_with_sol<'info>(
+    ctx: Context<'_, '_, 'info, 'info, PurchaseSol<'info>>,
     partner_code: String,
     amount: u64,
+    promo_code: String,
   ) -> Result<()> {
-    instructions::sale_handler::purchase_with_sol(ctx, partner_code, amount)
+    instructions::sale_handler::purchase_with_sol(ctx, partner_code, amount, promo_code)
   }
 
-  pub fn purchase_with_usdc(
-    ctx: Context<PurchaseUSDC>,
+  pub fn purchase_with_token<'info>(
+    ctx: Context<'_, '_, 'info, 'info, PurchaseToken<'info>>,
     partner_code: String,
     amount: u64,
+    promo_code: String,
+  ) -> Result<()> {
+    instructions::sale_handler::purchase_with_token(ctx, partner_code, amount, promo_code)
+  }
+
+  pub fn init_promo_code(
+    ctx: Context<InitPromoCode>,
+    _promo_code: String,
+    params: state::promo_code::PromoCodeParams,
+  ) -> Result<()> {
+    if !config::only_owners(ctx.accounts.payer.key()) {
+      return err!(errors::SaleHandler::Unauthorized);
+    }
+
+    instructions::promo_code::init_promo_code(ctx, params)
+  }
+
+  pub fn disable_promo_code(
+    ctx: Context<SetPromoCodeDisabled>,
+  ) -> Result<()> {
+    if !config::only_owners(ctx.accounts.payer.key()) {
+      return err!(errors::SaleHandler::Unauthorized);
+    }
+
+    instructions::promo_code::disable_promo_code(ctx)
+  }
+
+  pub fn init_escrow(
+    ctx: Context<InitEscrow>,
   ) -> Result<()> {
-    instructions::sale_handler::purchase_with_usdc(ctx, partner_code, amount)
+    if !config::only_owners(ctx.accounts.payer.key()) {
+      return err!(errors::SaleHandler::Unauthorized);
+    }
+
+    instructions::escrow::init_escrow(ctx)
   }
 
-  pub fn purchase_with_usdt(
-    ctx: Context<PurchaseUSDT>,
+  pub fn settle_partner(
+    ctx: Context<SettlePartner>,
     partner_code: String,
-    amount: u64,
   ) -> Result<()> {
-    instructions::sale_handler::purchase_with_usdt(ctx, partner_code, amount)
+    if !config::only_owners(ctx.accounts.payer.key()) {
+      return err!(errors::SaleHandler::Unauthorized);
+    }
+
+    instructions::escrow::settle_partner(ctx, partner_code)
+  }
+
+  pub fn clawback_sol_purchase<'info>(
+    ctx: Context<'_, '_, 'info, 'info, ClawbackSolPurchase<'info>>,
+    _purchaser: Pubkey,
+    _index: u64,
+  ) -> Result<()> {
+    if !config::only_owners(ctx.accounts.payer.key()) {
+      return err!(errors::SaleHandler::Unauthorized);
+    }
+
+    instructions::clawback::clawback_sol_purchase(ctx)
+  }
+
+  pub fn clawback_token_purchase<'info>(
+    ctx: Context<'_, '_, 'info, 'info, ClawbackTokenPurchase<'info>>,
+    _purchaser: Pubkey,
+    _index: u64,
+    partner_codes: Vec<String>,
+  ) -> Result<()> {
+    if !config::only_owners(ctx.accounts.payer.key()) {
+      return err!(errors::SaleHandler::Unauthorized);
+    }
+
+    instructions::clawback::clawback_token_purchase(ctx, partner_codes)
+  }
+
+  pub fn sweep_partner_rewards(
+    ctx: Context<SweepPartnerRewards>,
+    partner_code: String,
+  ) -> Result<()> {
+    if !config::only_owners(ctx.accounts.payer.key()) {
+      return err!(errors::SaleHandler::Unauthorized);
+    }
+
+    instructions::partner::sweep_partner_rewards(ctx, partner_code)
+  }
+
+  pub fn sweep_partner_token_reward(
+    ctx: Context<SweepPartnerTokenReward>,
+    partner_code: String,
+  ) -> Result<()> {
+    if !config::only_owners(ctx.accounts.payer.key()) {
+      return err!(errors::SaleHandler::Unauthorized);
+    }
+
+    instructions::partner::sweep_partner_token_reward(ctx, partner_code)
+  }
+
+  pub fn payout_partner_rewards<'info>(
+    ctx: Context<'_, '_, 'info, 'info, PayoutPartnerRewards<'info>>,
+    partner_codes: Vec<String>,
+  ) -> Result<()> {
+    if !config::only_owners(ctx.accounts.payer.key()) {
+      return err!(errors::SaleHandler::Unauthorized);
+    }
+
+    instructions::partner::payout_partner_rewards(ctx, partner_codes)
+  }
+
+  pub fn payout_partner_token_rewards<'info>(
+    ctx: Context<'_, '_, 'info, 'info, PayoutPartnerTokenRewards<'info>>,
+    partner_codes: Vec<String>,
+  ) -> Result<()> {
+    if !config::only_owners(ctx.accounts.payer.key()) {
+      return err!(errors::SaleHandler::Unauthorized);
+    }
+
+    instructions::partner::payout_partner_token_rewards(ctx, partner_codes)
+  }
+
+  pub fn init_accepted_token(
+    ctx: Context<InitAcceptedToken>,
+    price_update: Pubkey,
+    feed_id: [u8; 32],
+    fixed_price: u64,
+    max_cap: u64,
+    min_cap: u64,
+  ) -> Result<()> {
+    if !config::only_owners(ctx.accounts.payer.key()) {
+      return err!(errors::SaleHandler::Unauthorized);
+    }
+
+    instructions::accepted_token::init_accepted_token(ctx, price_update, feed_id, fixed_price, max_cap, min_cap)
+  }
+
+  pub fn set_accepted_token_price_source(
+    ctx: Context<SetAcceptedToken>,
+    price_update: Pubkey,
+    feed_id: [u8; 32],
+    fixed_price: u64,
+  ) -> Result<()> {
+    if !config::only_owners(ctx.accounts.payer.key()) {
+      return err!(errors::SaleHandler::Unauthorized);
+    }
+
+    instructions::accepted_token::set_accepted_token_price_source(ctx, price_update, feed_id, fixed_price)
+  }
+
+  pub fn set_accepted_token_cap(
+    ctx: Context<SetAcceptedToken>,
+    max_cap: u64,
+    min_cap: u64,
+  ) -> Result<()> {
+    if !config::only_owners(ctx.accounts.payer.key()) {
+      return err!(errors::SaleHandler::Unauthorized);
+    }
+
+    instructions::accepted_token::set_accepted_token_cap(ctx, max_cap, min_cap)
+  }
+
+  pub fn enable_accepted_token(
+    ctx: Context<SetAcceptedToken>,
+  ) -> Result<()> {
+    if !config::only_owners(ctx.accounts.payer.key()) {
+      return err!(errors::SaleHandler::Unauthorized);
+    }
+
+    instructions::accepted_token::enable_accepted_token(ctx)
+  }
+
+  pub fn disable_accepted_token(
+    ctx: Context<SetAcceptedToken>,
+  ) -> Result<()> {
+    if !config::only_owners(ctx.accounts.payer.key()) {
+      return err!(errors::SaleHandler::Unauthorized);
+    }
+
+    instructions::accepted_token::disable_accepted_token(ctx)
+  }
+
+  pub fn settle_partner_token(
+    ctx: Context<SettlePartnerToken>,
+    partner_code: String,
+  ) -> Result<()> {
+    if !config::only_owners(ctx.accounts.payer.key()) {
+      return err!(errors::SaleHandler::Unauthorized);
+    }
+
+    instructions::escrow::settle_partner_token(ctx, partner_code)
   }
 
   pub fn init_step(
@@ -165,6 +512,7 @@ use signature::check_sign;
   pub fn init_partner(
     ctx: Context<InitPartner>,
     _partner_code: String,
+    owner: Pubkey,
     main_interest: u64,
     secondary_interest: u64,
   ) -> Result<()> {
@@ -172,7 +520,60 @@ use signature::check_sign;
       return err!(errors::SaleHandler::Unauthorized);
     }
 
-    instructions::partner::init_partner(ctx, main_interest, secondary_interest)
+    instructions::partner::init_partner(ctx, owner, main_interest, secondary_interest)
+  }
+
+  pub fn register_partner(
+    ctx: Context<RegisterPartner>,
+    partner_code: String,
+    deadline: u128,
+    sig: Option<[u8; 64]>,
+    idx: u32,
+  ) -> Result<()> {
+    let invited = match sig {
+      Some(sig) => {
+        let signer_set = ctx.accounts.signer_set.as_deref().ok_or(errors::SaleHandler::SignerSetMissing)?;
+        check_invitation(idx, &partner_code, signer_set, &ctx.accounts.payer, sig, &ctx.accounts.ix_sysvar, deadline)?;
+        true
+      },
+      None => false,
+    };
+
+    instructions::partner::register_partner(ctx, partner_code, invited)
+  }
+
+  pub fn set_sale_handler_partner_registration(
+    ctx: Context<SetSaleHandlerPartnerRegistration>,
+    partner_registration: state::sale_handler::PartnerRegistration,
+  ) -> Result<()> {
+    if !config::only_owners(ctx.accounts.payer.key()) {
+      return err!(errors::SaleHandler::Unauthorized);
+    }
+
+    instructions::sale_handler::set_sale_handler_partner_registration(ctx, partner_registration)
+  }
+
+  pub fn transfer_partner_ownership(
+    ctx: Context<TransferPartnerOwnership>,
+    partner_code: String,
+    new_owner: Pubkey,
+  ) -> Result<()> {
+    if !config::only_owners(ctx.accounts.payer.key()) && !ctx.accounts.partner.is_owner(ctx.accounts.payer.key()) {
+      return err!(errors::SaleHandler::Unauthorized);
+    }
+
+    instructions::partner::transfer_partner_ownership(ctx, partner_code, new_owner)
+  }
+
+  pub fn set_partner_signed_claims(
+    ctx: Context<SetPartnerSignedClaims>,
+    signed_claims: bool,
+  ) -> Result<()> {
+    if !config::only_owners(ctx.accounts.payer.key()) {
+      return err!(errors::SaleHandler::Unauthorized);
+    }
+
+    instructions::partner::set_partner_signed_claims(ctx, signed_claims)
   }
 
   pub fn set_partner_interest(
@@ -187,6 +588,51 @@ use signature::check_sign;
     instructions::partner::set_partner_interest(ctx, main_interest, secondary_interest)
   }
 
+  pub fn set_partner_parent(
+    ctx: Context<SetPartnerParent>,
+  ) -> Result<()> {
+    if !config::only_owners(ctx.accounts.payer.key()) {
+      return err!(errors::SaleHandler::Unauthorized);
+    }
+
+    instructions::partner::set_partner_parent(ctx)
+  }
+
+  pub fn set_partner_buyer_bonus(
+    ctx: Context<SetPartnerBuyerBonus>,
+    buyer_bonus: u64,
+  ) -> Result<()> {
+    if !config::only_owners(ctx.accounts.payer.key()) && !ctx.accounts.partner.is_owner(ctx.accounts.payer.key()) {
+      return err!(errors::SaleHandler::Unauthorized);
+    }
+
+    instructions::partner::set_partner_buyer_bonus(ctx, buyer_bonus)
+  }
+
+  pub fn set_purchaser_partner(
+    ctx: Context<SetPurchaserPartner>,
+    wallet: Pubkey,
+    partner_code: String,
+  ) -> Result<()> {
+    if !config::only_owners(ctx.accounts.payer.key()) {
+      return err!(errors::SaleHandler::Unauthorized);
+    }
+
+    instructions::partner::set_purchaser_partner(ctx, wallet, partner_code)
+  }
+
+  pub fn set_partner_limits(
+    ctx: Context<SetPartnerLimits>,
+    linked_wallets: Vec<Pubkey>,
+    commission_cap_usd: u128,
+  ) -> Result<()> {
+    if !config::only_owners(ctx.accounts.payer.key()) {
+      return err!(errors::SaleHandler::Unauthorized);
+    }
+
+    instructions::partner::set_partner_limits(ctx, linked_wallets, commission_cap_usd)
+  }
+
   pub fn enable_partner(
     ctx: Context<SetPartnerEnabled>,
   ) -> Result<()> {
@@ -210,33 +656,66 @@ use signature::check_sign;
   pub fn receive_sol(
     ctx: Context<ReceiveSol>,
     partner: String,
+    amount_cap: u128,
     deadline: u128,
-    sig: [u8; 64],
+    sig: Option<[u8; 64]>,
     idx: u32,
   ) -> Result<()> {
-    check_sign(idx, &partner, &ctx.accounts.payer, sig, &ctx.accounts.ix_sysvar, deadline).unwrap();
-    instructions::partner::receive_sol(ctx, partner)
+    let request = ClaimRequest { currency: ClaimCurrency::Sol, amount_cap, deadline, sig, index: idx };
+    check_claim(&partner, &mut ctx.accounts.partner, ctx.accounts.signer_set.as_deref(), &ctx.accounts.payer, &ctx.accounts.ix_sysvar, request)?;
+    instructions::partner::receive_sol(ctx, partner, amount_cap)
   }
 
   pub fn receive_usdc(
     ctx: Context<ReceiveUSDC>,
     partner: String,
+    amount_cap: u128,
     deadline: u128,
-    sig: [u8; 64],
+    sig: Option<[u8; 64]>,
     idx: u32,
   ) -> Result<()> {
-    check_sign(idx, &partner, &ctx.accounts.payer, sig, &ctx.accounts.ix_sysvar, deadline).unwrap();
-    instructions::partner::receive_usdc(ctx, partner)
+    let request = ClaimRequest { currency: ClaimCurrency::Usdc, amount_cap, deadline, sig, index: idx };
+    check_claim(&partner, &mut ctx.accounts.partner, ctx.accounts.signer_set.as_deref(), &ctx.accounts.payer, &ctx.accounts.ix_sysvar, request)?;
+    instructions::partner::receive_usdc(ctx, partner, amount_cap)
   }
 
   pub fn receive_usdt(
     ctx: Context<ReceiveUSDT>,
     partner: String,
+    amount_cap: u128,
+    deadline: u128,
+    sig: Option<[u8; 64]>,
+    idx: u32,
+  ) -> Result<()> {
+    let request = ClaimRequest { currency: ClaimCurrency::Usdt, amount_cap, deadline, sig, index: idx };
+    check_claim(&partner, &mut ctx.accounts.partner, ctx.accounts.signer_set.as_deref(), &ctx.accounts.payer, &ctx.accounts.ix_sysvar, request)?;
+    instructions::partner::receive_usdt(ctx, partner, amount_cap)
+  }
+
+  pub fn receive_payment_token(
+    ctx: Context<ReceivePaymentToken>,
+    partner: String,
+    amount_cap: u128,
+    deadline: u128,
+    sig: Option<[u8; 64]>,
+    idx: u32,
+  ) -> Result<()> {
+    let currency = ClaimCurrency::Spl(ctx.accounts.mint.key());
+    let request = ClaimRequest { currency, amount_cap, deadline, sig, index: idx };
+    check_claim(&partner, &mut ctx.accounts.partner, ctx.accounts.signer_set.as_deref(), &ctx.accounts.payer, &ctx.accounts.ix_sysvar, request)?;
+    instructions::partner::receive_payment_token(ctx, partner, amount_cap)
+  }
+
+  pub fn receive_tokens(
+    ctx: Context<ReceiveTokens>,
+    partner: String,
+    amount_cap: u128,
     deadline: u128,
-    sig: [u8; 64],
+    sig: Option<[u8; 64]>,
     idx: u32,
   ) -> Result<()> {
-    check_sign(idx, &partner, &ctx.accounts.payer, sig, &ctx.accounts.ix_sysvar, deadline).unwrap();
-    instructions::partner::receive_usdt(ctx, partner)
+    let request = ClaimRequest { currency: ClaimCurrency::Tokens, amount_cap, deadline, sig, index: idx };
+    check_claim(&partner, &mut ctx.accounts.partner, ctx.accounts.signer_set.as_deref(), &ctx.accounts.payer, &ctx.accounts.ix_sysvar, request)?;
+    instructions::partner::receive_tokens(ctx, partner, amount_cap)
   }
 }
@@ -1,103 +1,354 @@
 use anchor_lang::prelude::*;
 use solana_program::instruction::Instruction;
 use solana_program::ed25519_program::ID as ED25519_ID;
-use solana_program::sysvar::instructions::load_instruction_at_checked;
-
-use std::convert::TryInto;
+use solana_program::sysvar::instructions::{ load_current_index_checked, load_instruction_at_checked };
 
 use crate::{config, errors};
+use crate::state::partner::Partner;
+use crate::state::signer_set::SignerSet;
 
-pub fn check_sign(
+const CLAIM_KIND: &str = "receive";
+const INVITATION_KIND: &str = "register";
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum ClaimCurrency {
+  Sol,
+  Usdc,
+  Usdt,
+  Tokens,
+  // Accepted payment token, identified by its mint
+  Spl(Pubkey),
+}
+
+impl std::fmt::Display for ClaimCurrency {
+  fn fmt(
+    &self,
+    f: &mut std::fmt::Formatter,
+  ) -> std::fmt::Result {
+    match self {
+      ClaimCurrency::Sol => write!(f, "SOL"),
+      ClaimCurrency::Usdc => write!(f, "USDC"),
+      ClaimCurrency::Usdt => write!(f, "USDT"),
+      ClaimCurrency::Tokens => write!(f, "TOKENS"),
+      ClaimCurrency::Spl(mint) => write!(f, "{}", mint),
+    }
+  }
+}
+
+/// Claim arguments covered by the backend signature
+pub struct ClaimRequest {
+  pub currency: ClaimCurrency,
+  pub amount_cap: u128,
+  pub deadline: u128,
+  pub sig: Option<[u8; 64]>,
+  pub index: u32,
+}
+
+/// Owner claims directly; anyone else needs a backend signature when the partner allows it
+pub fn check_claim(
+  code: &String,
+  partner: &mut Partner,
+  signer_set: Option<&SignerSet>,
+  payer: &Signer,
+  ix_sysvar: &AccountInfo,
+  request: ClaimRequest,
+) -> Result<()> {
+  if partner.is_owner(payer.key()) {
+    return Ok(());
+  }
+
+  match request.sig {
+    Some(sig) if partner.is_signed_claims() => {
+      let signer_set = signer_set.ok_or(errors::SaleHandler::SignerSetMissing)?;
+      let message = claim_message(code, &payer.key(), request.currency, request.amount_cap, partner.get_nonce(), request.deadline);
+      check_sign(request.index, &message, signer_set, sig, ix_sysvar, request.deadline)?;
+      // Every signature is single-use
+      partner.increment_nonce()
+    },
+    _ => err!(errors::SaleHandler::Unauthorized),
+  }
+}
+
+/// Versioned claim message, bound to this program, the action and the partner nonce
+pub fn claim_message(
+  code: &String,
+  payer: &Pubkey,
+  currency: ClaimCurrency,
+  amount_cap: u128,
+  nonce: u64,
+  deadline: u128,
+) -> String {
+  format!(
+    "{}|{}|{}|{}|{}|{}|{}|{}|{}",
+    config::SIGNATURE_VERSION,
+    crate::ID,
+    CLAIM_KIND,
+    currency,
+    code,
+    payer,
+    amount_cap,
+    nonce,
+    deadline,
+  )
+}
+
+/// Backend invitation allowing `payer` to register `code` as its own partner code
+pub fn check_invitation(
   index: u32,
   code: &String,
+  signer_set: &SignerSet,
   payer: &Signer,
   sig: [u8; 64],
   ix_sysvar: &AccountInfo,
   deadline: u128,
 ) -> Result<()> {
-  let idx = usize::try_from(index).unwrap();
+  let message = invitation_message(code, &payer.key(), deadline);
+  check_sign(index, &message, signer_set, sig, ix_sysvar, deadline)
+}
+
+/// Versioned invitation message, bound to this program and the registering wallet
+pub fn invitation_message(
+  code: &String,
+  payer: &Pubkey,
+  deadline: u128,
+) -> String {
+  format!(
+    "{}|{}|{}|{}|{}|{}",
+    config::SIGNATURE_VERSION,
+    crate::ID,
+    INVITATION_KIND,
+    code,
+    payer,
+    deadline,
+  )
+}
+
+pub fn check_sign(
+  index: u32,
+  message: &String,
+  signer_set: &SignerSet,
+  sig: [u8; 64],
+  ix_sysvar: &AccountInfo,
+  deadline: u128,
+) -> Result<()> {
+  // The Ed25519 instruction must precede the current one in the same transaction
+  let current = load_current_index_checked(&ix_sysvar)?;
+  if index >= u32::from(current) {
+    return err!(errors::SaleHandler::SignatureVerificationFailedWrongIndex);
+  }
+
+  let idx = index as usize;
   let ix: Instruction = load_instruction_at_checked(idx, &ix_sysvar)?;
-  // Check that ix is what we expect to have been sent
-  let message = format!("{}{}{}", code, payer.key(), deadline);
-  let pubkey = config::SIGNATURE_SIGNER.parse::<Pubkey>().unwrap();
 
   let clock: Clock = Clock::get()?;
-  let now: u128 = clock.unix_timestamp.try_into().unwrap();
-  if now > deadline {
+  if clock.unix_timestamp < 0 || clock.unix_timestamp as u128 > deadline {
     return err!(errors::SaleHandler::ExpiredSignature); 
   }
 
-  verify_ed25519(&ix, &pubkey.key().to_bytes(), &message.as_bytes(), &sig).unwrap();
+  let entries = parse_ed25519(&ix, idx)?;
 
-  Ok(())
-}
+  // Any key inside its validity window is accepted, so rotations can overlap
+  for pubkey in signer_set.get_valid_signers(clock.unix_timestamp) {
+    let found = entries.iter().any(|entry| {
+      entry.pubkey  == pubkey.as_ref()      &&
+      entry.msg     == message.as_bytes()   &&
+      entry.sig     == &sig[..]
+    });
 
-/// Verify Ed25519Program instruction fields
-fn verify_ed25519(ix: &Instruction, pubkey: &[u8], msg: &[u8], sig: &[u8]) -> Result<()> {
-  if  ix.program_id       != ED25519_ID                   ||  // The program id we expect
-      ix.accounts.len()   != 0                            ||  // With no context accounts
-      ix.data.len()       != (16 + 64 + 32 + msg.len())       // And data of this size
-  {
-    return err!(errors::SaleHandler::SignatureVerificationFailed);    // Otherwise, we can already throw err
+    if found {
+      return Ok(());
+    }
   }
 
-  check_ed25519_data(&ix.data, pubkey, msg, sig)?;            // If that's not the case, check data
+  err!(errors::SaleHandler::SignatureVerificationFailedNoMatch)
+}
 
-  Ok(())
+/// Single signature entry of an Ed25519Program instruction
+struct Ed25519Entry<'a> {
+  pubkey: &'a [u8],
+  sig: &'a [u8],
+  msg: &'a [u8],
 }
 
-/// Verify serialized Ed25519Program instruction data
-fn check_ed25519_data(data: &[u8], pubkey: &[u8], msg: &[u8], sig: &[u8]) -> Result<()> {
+const ED25519_HEADER_SIZE: usize    = 2;  // num_signatures: u8, padding: u8
+const ED25519_OFFSETS_SIZE: usize   = 14; // 7 * u16
+const ED25519_PUBKEY_SIZE: usize    = 32;
+const ED25519_SIGNATURE_SIZE: usize = 64;
+
+/// Verify Ed25519Program instruction fields and deserialize every signature entry
+fn parse_ed25519(ix: &Instruction, idx: usize) -> Result<Vec<Ed25519Entry<'_>>> {
+  if  ix.program_id       != ED25519_ID   ||  // The program id we expect
+      ix.accounts.len()   != 0                // With no context accounts
+  {
+    return err!(errors::SaleHandler::SignatureVerificationFailedWrongProgram);
+  }
+
   // According to this layout used by the Ed25519Program
   // https://github.com/solana-labs/solana-web3.js/blob/master/src/ed25519-program.ts#L33
+  let data = &ix.data;
+  if data.len() < ED25519_HEADER_SIZE || data[1] != 0 {
+    return err!(errors::SaleHandler::SignatureVerificationFailedMalformedData);
+  }
 
-  // "Deserializing" byte slices
-
-  let num_signatures                  = &[data[0]];        // Byte  0
-  let padding                         = &[data[1]];        // Byte  1
-  let signature_offset                = &data[2..=3];      // Bytes 2,3
-  let signature_instruction_index     = &data[4..=5];      // Bytes 4,5
-  let public_key_offset               = &data[6..=7];      // Bytes 6,7
-  let public_key_instruction_index    = &data[8..=9];      // Bytes 8,9
-  let message_data_offset             = &data[10..=11];    // Bytes 10,11
-  let message_data_size               = &data[12..=13];    // Bytes 12,13
-  let message_instruction_index       = &data[14..=15];    // Bytes 14,15
-
-  let data_pubkey                     = &data[16..16+32];  // Bytes 16..16+32
-  let data_sig                        = &data[48..48+64];  // Bytes 48..48+64
-  let data_msg                        = &data[112..];      // Bytes 112..end
-
-  // Expected values
-
-  let exp_public_key_offset:      u16 = 16; // 2*u8 + 7*u16
-  let exp_signature_offset:       u16 = exp_public_key_offset + pubkey.len() as u16;
-  let exp_message_data_offset:    u16 = exp_signature_offset + sig.len() as u16;
-  let exp_num_signatures:         u8 = 1;
-  let exp_message_data_size:      u16 = msg.len().try_into().unwrap();
-
-  // Header and Arg Checks
-
-  // Header
-  if  num_signatures                  != &exp_num_signatures.to_le_bytes()        ||
-      padding                         != &[0]                                     ||
-      signature_offset                != &exp_signature_offset.to_le_bytes()      ||
-      signature_instruction_index     != &u16::MAX.to_le_bytes()                  ||
-      public_key_offset               != &exp_public_key_offset.to_le_bytes()     ||
-      public_key_instruction_index    != &u16::MAX.to_le_bytes()                  ||
-      message_data_offset             != &exp_message_data_offset.to_le_bytes()   ||
-      message_data_size               != &exp_message_data_size.to_le_bytes()     ||
-      message_instruction_index       != &u16::MAX.to_le_bytes()  
-  {
-    return err!(errors::SaleHandler::SignatureVerificationFailed);
+  let num_signatures = usize::from(data[0]);
+  if num_signatures == 0 || data.len() < ED25519_HEADER_SIZE + num_signatures * ED25519_OFFSETS_SIZE {
+    return err!(errors::SaleHandler::SignatureVerificationFailedMalformedData);
   }
 
-  // Arguments
-  if  data_pubkey != pubkey   ||
-      data_msg    != msg      ||
-      data_sig    != sig
-  {
-    return err!(errors::SaleHandler::SignatureVerificationFailed);
+  let mut entries = Vec::with_capacity(num_signatures);
+  for n in 0..num_signatures {
+    let start = ED25519_HEADER_SIZE + n * ED25519_OFFSETS_SIZE;
+    let offsets = &data[start..start + ED25519_OFFSETS_SIZE];
+
+    let signature_offset                = read_u16(offsets, 0);
+    let signature_instruction_index     = read_u16(offsets, 2);
+    let public_key_offset               = read_u16(offsets, 4);
+    let public_key_instruction_index    = read_u16(offsets, 6);
+    let message_data_offset             = read_u16(offsets, 8);
+    let message_data_size               = read_u16(offsets, 10);
+    let message_instruction_index       = read_u16(offsets, 12);
+
+    // All data has to live inside the Ed25519 instruction itself
+    if  !is_own_instruction(signature_instruction_index, idx)    ||
+        !is_own_instruction(public_key_instruction_index, idx)   ||
+        !is_own_instruction(message_instruction_index, idx)
+    {
+      return err!(errors::SaleHandler::SignatureVerificationFailedForeignData);
+    }
+
+    entries.push(Ed25519Entry {
+      pubkey: read_slice(data, public_key_offset, ED25519_PUBKEY_SIZE)?,
+      sig: read_slice(data, signature_offset, ED25519_SIGNATURE_SIZE)?,
+      msg: read_slice(data, message_data_offset, message_data_size)?,
+    });
+  }
+
+  Ok(entries)
+}
+
+fn read_u16(data: &[u8], offset: usize) -> usize {
+  usize::from(u16::from_le_bytes([data[offset], data[offset + 1]]))
+}
+
+fn read_slice(data: &[u8], offset: usize, size: usize) -> Result<&[u8]> {
+  match data.get(offset..offset + size) {
+    Some(slice) => Ok(slice),
+    None => err!(errors::SaleHandler::SignatureVerificationFailedMalformedData),
+  }
+}
+
+fn is_own_instruction(instruction_index: usize, idx: usize) -> bool {
+  instruction_index == usize::from(u16::MAX) || instruction_index == idx
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  const OWN: u16 = u16::MAX;
+
+  /// Ed25519Program data with every entry stored after the offsets block
+  fn ed25519_data(entries: &[(&[u8; 32], &[u8; 64], &[u8])], index: u16) -> Vec<u8> {
+    let mut offsets = Vec::new();
+    let mut payload = Vec::new();
+    let base = ED25519_HEADER_SIZE + entries.len() * ED25519_OFFSETS_SIZE;
+
+    for (pubkey, sig, msg) in entries {
+      let public_key_offset = (base + payload.len()) as u16;
+      payload.extend_from_slice(&pubkey[..]);
+      let signature_offset = (base + payload.len()) as u16;
+      payload.extend_from_slice(&sig[..]);
+      let message_data_offset = (base + payload.len()) as u16;
+      payload.extend_from_slice(msg);
+
+      for value in [signature_offset, index, public_key_offset, index, message_data_offset, msg.len() as u16, index] {
+        offsets.extend_from_slice(&value.to_le_bytes());
+      }
+    }
+
+    let mut data = vec![entries.len() as u8, 0];
+    data.extend(offsets);
+    data.extend(payload);
+    data
+  }
+
+  fn ed25519_ix(data: Vec<u8>) -> Instruction {
+    Instruction { program_id: ED25519_ID, accounts: vec![], data }
+  }
+
+  fn parse_err(ix: &Instruction, idx: usize) -> Error {
+    match parse_ed25519(ix, idx) {
+      Ok(_) => panic!("expected an error"),
+      Err(error) => error,
+    }
+  }
+
+  #[test]
+  fn parses_single_entry() {
+    let ix = ed25519_ix(ed25519_data(&[(&[1; 32], &[2; 64], b"message")], OWN));
+    let entries = parse_ed25519(&ix, 0).unwrap();
+
+    assert_eq!(entries.len(), 1);
+    assert_eq!(entries[0].pubkey, &[1; 32][..]);
+    assert_eq!(entries[0].sig, &[2; 64][..]);
+    assert_eq!(entries[0].msg, b"message");
+  }
+
+  #[test]
+  fn parses_multiple_entries() {
+    let ix = ed25519_ix(ed25519_data(&[(&[1; 32], &[2; 64], b"first"), (&[3; 32], &[4; 64], b"second")], OWN));
+    let entries = parse_ed25519(&ix, 0).unwrap();
+
+    assert_eq!(entries.len(), 2);
+    assert_eq!(entries[1].pubkey, &[3; 32][..]);
+    assert_eq!(entries[1].sig, &[4; 64][..]);
+    assert_eq!(entries[1].msg, b"second");
+  }
+
+  #[test]
+  fn accepts_explicit_own_index() {
+    let ix = ed25519_ix(ed25519_data(&[(&[1; 32], &[2; 64], b"message")], 3));
+    assert!(parse_ed25519(&ix, 3).is_ok());
+  }
+
+  #[test]
+  fn rejects_other_program() {
+    let mut ix = ed25519_ix(ed25519_data(&[(&[1; 32], &[2; 64], b"message")], OWN));
+    ix.program_id = crate::ID;
+
+    assert_eq!(parse_err(&ix, 0), errors::SaleHandler::SignatureVerificationFailedWrongProgram.into());
   }
 
-  Ok(())
+  #[test]
+  fn rejects_context_accounts() {
+    let mut ix = ed25519_ix(ed25519_data(&[(&[1; 32], &[2; 64], b"message")], OWN));
+    ix.accounts.push(AccountMeta::new_readonly(Pubkey::default(), false));
+
+    assert_eq!(parse_err(&ix, 0), errors::SaleHandler::SignatureVerificationFailedWrongProgram.into());
+  }
+
+  #[test]
+  fn rejects_malformed_header() {
+    let malformed = errors::SaleHandler::SignatureVerificationFailedMalformedData;
+
+    assert_eq!(parse_err(&ed25519_ix(vec![]), 0), malformed.into());
+    assert_eq!(parse_err(&ed25519_ix(vec![0, 0]), 0), malformed.into());
+    assert_eq!(parse_err(&ed25519_ix(vec![1, 1]), 0), malformed.into());
+    assert_eq!(parse_err(&ed25519_ix(vec![2, 0, 0, 0]), 0), malformed.into());
+  }
+
+  #[test]
+  fn rejects_foreign_data() {
+    let ix = ed25519_ix(ed25519_data(&[(&[1; 32], &[2; 64], b"message")], 1));
+
+    assert_eq!(parse_err(&ix, 0), errors::SaleHandler::SignatureVerificationFailedForeignData.into());
+  }
+
+  #[test]
+  fn rejects_out_of_bounds_entry() {
+    let mut data = ed25519_data(&[(&[1; 32], &[2; 64], b"message")], OWN);
+    data.truncate(data.len() - 1);
+
+    assert_eq!(parse_err(&ed25519_ix(data), 0), errors::SaleHandler::SignatureVerificationFailedMalformedData.into());
+  }
 }
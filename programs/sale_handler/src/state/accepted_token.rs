@@ -0,0 +1,131 @@
+use anchor_lang::prelude::*;
+use crate::errors;
+
+#[account]
+pub struct AcceptedToken {
+  mint: Pubkey,
+  // Pyth price update account and feed; a non-zero `fixed_price` pegs the token, the feed then only guards the peg
+  price_update: Pubkey,
+  feed_id: [u8; 32],
+  fixed_price: u64,
+  max_cap: u64,
+  min_cap: u64,
+  enabled: bool,
+}
+
+impl AcceptedToken {
+  pub const MAX_SIZE: usize = 32 + 32 + 32 + (3 * 8) + 1;
+
+  // Price source and caps are set right after through `set_price_source` and `set_cap`
+  pub fn init(
+    &mut self,
+    mint: Pubkey,
+  ) -> Result<()> {
+    self.mint = mint;
+    self.enabled = false;
+
+    Ok(())
+  }
+
+  pub fn set_price_source(
+    &mut self,
+    price_update: Pubkey,
+    feed_id: [u8; 32],
+    fixed_price: u64,
+  ) -> Result<()> {
+    if price_update == Pubkey::default() && fixed_price == 0 {
+      return err!(errors::SaleHandler::WrongAcceptedTokenPrice);
+    }
+
+    self.price_update = price_update;
+    self.feed_id = feed_id;
+    self.fixed_price = fixed_price;
+
+    Ok(())
+  }
+
+  pub fn set_cap(
+    &mut self,
+    max_cap: u64,
+    min_cap: u64,
+  ) -> Result<()> {
+    if min_cap > max_cap {
+      return err!(errors::SaleHandler::WrongAcceptedTokenCap);
+    }
+
+    self.max_cap = max_cap;
+    self.min_cap = min_cap;
+
+    Ok(())
+  }
+
+  pub fn set_enable(
+    &mut self,
+  ) -> Result<()> {
+    self.enabled = true;
+
+    Ok(())
+  }
+
+  pub fn set_disable(
+    &mut self,
+  ) -> Result<()> {
+    self.enabled = false;
+
+    Ok(())
+  }
+
+  pub fn get_mint(
+    &self,
+  ) -> Pubkey {
+    self.mint
+  }
+
+  pub fn get_price_update(
+    &self,
+  ) -> Pubkey {
+    self.price_update
+  }
+
+  pub fn get_feed_id(
+    &self,
+  ) -> [u8; 32] {
+    self.feed_id
+  }
+
+  pub fn get_fixed_price(
+    &self,
+  ) -> u64 {
+    self.fixed_price
+  }
+
+  pub fn get_max_cap(
+    &self,
+  ) -> u128 {
+    u128::from(self.max_cap)
+  }
+
+  pub fn get_min_cap(
+    &self,
+  ) -> u128 {
+    u128::from(self.min_cap)
+  }
+
+  pub fn is_pegged(
+    &self,
+  ) -> bool {
+    self.fixed_price > 0
+  }
+
+  pub fn has_price_feed(
+    &self,
+  ) -> bool {
+    self.price_update != Pubkey::default()
+  }
+
+  pub fn is_enabled(
+    &self,
+  ) -> bool {
+    self.enabled
+  }
+}
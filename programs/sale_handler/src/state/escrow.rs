@@ -0,0 +1,44 @@
+use anchor_lang::prelude::*;
+
+#[account]
+pub struct Escrow {
+  // Deferred SOL commissions currently held, not yet settled to partners,
+  // accepted token ones are tracked per mint in `PartnerTokenReward`
+  sol_amount: u64,
+}
+
+impl Escrow {
+  pub const MAX_SIZE: usize = 8;
+
+  pub fn init(
+    &mut self,
+  ) -> Result<()> {
+    self.sol_amount = 0;
+
+    Ok(())
+  }
+
+  pub fn set_sol_amount(
+    &mut self,
+    amount: u64,
+  ) -> Result<()> {
+    self.sol_amount += amount;
+
+    Ok(())
+  }
+
+  pub fn release(
+    &mut self,
+    sol_amount: u64,
+  ) -> Result<()> {
+    self.sol_amount -= sol_amount;
+
+    Ok(())
+  }
+
+  pub fn get_sol_amount(
+    &self,
+  ) -> u64 {
+    self.sol_amount
+  }
+}
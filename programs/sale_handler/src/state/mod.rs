@@ -1,4 +1,9 @@
 pub mod sale_handler;
 pub mod step;
 pub mod partner;
-pub mod purchaser;
\ No newline at end of file
+pub mod purchaser;
+pub mod signer_set;
+pub mod promo_code;
+pub mod escrow;
+pub mod accepted_token;
+pub mod partner_token_reward;pub mod purchase_record;
@@ -1,39 +1,208 @@
 use anchor_lang::prelude::*;
+use crate::errors;
+use crate::config::MAX_LINKED_WALLETS;
 
 #[account]
 pub struct Partner {
+  registered: bool,
+  owner: Pubkey,
+  signed_claims: bool,
+  nonce: u64,
+  parent: Pubkey,
+  // Wallets of the owner that cannot earn commission on their own purchases
+  linked_wallets: Vec<Pubkey>,
+  // USD value of main interest commissions, 0 cap means unlimited
+  commission_usd: u128,
+  commission_cap_usd: u128,
+
   main_interest: u64,
   secondary_interest: u64,
+  buyer_bonus: u64,
 
   sol_reward: u64,
+  // Legacy USDC and USDT balances of the first deployment, only claimed, paid out or swept,
+  // accepted token commissions live in `PartnerTokenReward`
   usdt_reward: u64,
   usdc_reward: u64,
   token_reward: u128,
-
+  token_reward_claimed: u128,
+
+  // Commissions held in escrow until the holding period since `deferred_at` passes
+  deferred_sol_reward: u64,
+  deferred_at: i64,
+  // Older deferred commissions, set aside so newer ones cannot postpone them
+  sealed_sol_reward: u64,
+  sealed_at: i64,
+
+  // Clawed back commissions already claimed, offset against future earnings
+  sol_debt: u64,
+  token_debt: u128,
+
+  referred_volume: u128,
+  tier: u8,
+
+  // Lifetime counters, never reset
+  referred_purchases: u64,
+  referred_buyers: u64,
+  sol_volume_usd: u128,
+  spl_volume_usd: u128,
+  sol_earned: u64,
+  usdc_earned: u64,
+  usdt_earned: u64,
+  sol_claimed: u64,
+  usdc_claimed: u64,
+  usdt_claimed: u64,
+  sol_swept: u64,
+  usdc_swept: u64,
+  usdt_swept: u64,
+
+  // Registered under approval mode and not yet enabled by an admin
+  pending: bool,
   enabled: bool,
 }
 
 impl Partner {
-  pub const MAX_SIZE: usize = (5 * 8) + 16 + 1 + 3;
+  pub const MAX_SIZE: usize = 1 + 32 + 1 + 8 + 32 + (4 + 32 * MAX_LINKED_WALLETS) + (2 * 16) + (6 * 8) + (2 * 16) + (2 * 8) + (2 * 8) + 8 + 16 + 16 + 1 + (2 * 8) + (2 * 16) + (9 * 8) + 1 + 1 + 3;
 
   pub fn init(
     &mut self,
+    owner: Pubkey,
     main_interest: u64,
     secondary_interest: u64,
   ) -> Result<()> {
+    self.registered = true;
+    self.owner = owner;
+    self.signed_claims = false;
+    self.nonce = 0;
+    self.parent = Pubkey::default();
+    self.linked_wallets = Vec::new();
+    self.commission_usd = 0;
+    self.commission_cap_usd = 0;
+
     self.main_interest = main_interest;
     self.secondary_interest = secondary_interest;
+    self.buyer_bonus = 0;
 
     self.sol_reward = 0;
     self.usdt_reward = 0;
     self.usdc_reward = 0;
     self.token_reward = 0;
-
+    self.token_reward_claimed = 0;
+
+    self.deferred_sol_reward = 0;
+    self.deferred_at = 0;
+    self.sealed_sol_reward = 0;
+    self.sealed_at = 0;
+
+    self.sol_debt = 0;
+    self.token_debt = 0;
+
+    self.referred_volume = 0;
+    self.tier = 0;
+
+    self.referred_purchases = 0;
+    self.referred_buyers = 0;
+    self.sol_volume_usd = 0;
+    self.spl_volume_usd = 0;
+    self.sol_earned = 0;
+    self.usdc_earned = 0;
+    self.usdt_earned = 0;
+    self.sol_claimed = 0;
+    self.usdc_claimed = 0;
+    self.usdt_claimed = 0;
+    self.sol_swept = 0;
+    self.usdc_swept = 0;
+    self.usdt_swept = 0;
+
+    self.pending = false;
     self.enabled = true;
 
     Ok(())
   }
 
+  /// Carries a partner of the first deployment over, ownerless partners keep claiming through signatures
+  pub fn migrate(
+    &mut self,
+    legacy: &LegacyPartner,
+    owner: Pubkey,
+    registered: bool,
+  ) -> Result<()> {
+    self.init(owner, legacy.main_interest, legacy.secondary_interest).unwrap();
+    self.registered = registered;
+    self.signed_claims = owner == Pubkey::default();
+
+    self.sol_reward = legacy.sol_reward;
+    self.usdt_reward = legacy.usdt_reward;
+    self.usdc_reward = legacy.usdc_reward;
+    self.token_reward = legacy.token_reward;
+    self.sol_earned = legacy.sol_reward;
+    self.usdc_earned = legacy.usdc_reward;
+    self.usdt_earned = legacy.usdt_reward;
+
+    self.enabled = legacy.enabled;
+
+    Ok(())
+  }
+
+  pub fn set_owner(
+    &mut self,
+    owner: Pubkey,
+  ) -> Result<()> {
+    self.owner = owner;
+
+    Ok(())
+  }
+
+  pub fn set_signed_claims(
+    &mut self,
+    signed_claims: bool,
+  ) -> Result<()> {
+    self.signed_claims = signed_claims;
+
+    Ok(())
+  }
+
+  pub fn increment_nonce(
+    &mut self,
+  ) -> Result<()> {
+    self.nonce += 1;
+
+    Ok(())
+  }
+
+  pub fn set_parent(
+    &mut self,
+    parent: Pubkey,
+  ) -> Result<()> {
+    self.parent = parent;
+
+    Ok(())
+  }
+
+  pub fn set_limits(
+    &mut self,
+    linked_wallets: Vec<Pubkey>,
+    commission_cap_usd: u128,
+  ) -> Result<()> {
+    if linked_wallets.len() > MAX_LINKED_WALLETS {
+      return err!(errors::SaleHandler::PartnerLinkedWalletsTooMany);
+    }
+
+    self.linked_wallets = linked_wallets;
+    self.commission_cap_usd = commission_cap_usd;
+
+    Ok(())
+  }
+
+  pub fn set_commission_usd(
+    &mut self,
+    amount: u128,
+  ) -> Result<()> {
+    self.commission_usd += amount;
+
+    Ok(())
+  }
+
   pub fn set_interest(
     &mut self,
     main_interest: u64,
@@ -45,11 +214,21 @@ impl Partner {
     Ok(())
   }
 
+  pub fn set_buyer_bonus(
+    &mut self,
+    buyer_bonus: u64,
+  ) -> Result<()> {
+    self.buyer_bonus = buyer_bonus;
+
+    Ok(())
+  }
+
   pub fn set_sol_reward(
     &mut self,
     amount: u64,
   ) -> Result<()> {
     self.sol_reward += amount;
+    self.sol_earned += amount;
 
     Ok(())
   }
@@ -57,16 +236,17 @@ impl Partner {
   pub fn reset_sol_reward(
     &mut self,
   ) -> Result<()> {
+    self.sol_claimed += self.sol_reward;
     self.sol_reward = 0;
 
     Ok(())
   }
 
-  pub fn set_usdt_reward(
+  pub fn sweep_sol_reward(
     &mut self,
-    amount: u64,
   ) -> Result<()> {
-    self.usdt_reward += amount;
+    self.sol_swept += self.sol_reward;
+    self.sol_reward = 0;
 
     Ok(())
   }
@@ -74,16 +254,17 @@ impl Partner {
   pub fn reset_usdt_reward(
     &mut self,
   ) -> Result<()> {
+    self.usdt_claimed += self.usdt_reward;
     self.usdt_reward = 0;
 
     Ok(())
   }
 
-  pub fn set_usdc_reward(
+  pub fn sweep_usdt_reward(
     &mut self,
-    amount: u64,
   ) -> Result<()> {
-    self.usdc_reward += amount;
+    self.usdt_swept += self.usdt_reward;
+    self.usdt_reward = 0;
 
     Ok(())
   }
@@ -91,26 +272,265 @@ impl Partner {
   pub fn reset_usdc_reward(
     &mut self,
   ) -> Result<()> {
+    self.usdc_claimed += self.usdc_reward;
     self.usdc_reward = 0;
 
     Ok(())
   }
 
+  pub fn sweep_usdc_reward(
+    &mut self,
+  ) -> Result<()> {
+    self.usdc_swept += self.usdc_reward;
+    self.usdc_reward = 0;
+
+    Ok(())
+  }
+
+  pub fn set_deferred_sol_reward(
+    &mut self,
+    amount: u64,
+    now: i64,
+  ) -> Result<()> {
+    if amount > 0 {
+      self.seal_deferred_sol_reward();
+      self.deferred_sol_reward += amount;
+      self.deferred_at = now;
+    }
+
+    Ok(())
+  }
+
+  /// Moves deferred commissions older than `holding_period` to the claimable rewards, returns the SOL amount
+  pub fn settle_deferred_sol_reward(
+    &mut self,
+    now: i64,
+    holding_period: i64,
+  ) -> Result<u64> {
+    if self.get_deferred_sol_reward() == 0 {
+      return err!(errors::SaleHandler::PartnerNoFunds);
+    }
+
+    let mut amount = 0;
+    if now >= self.sealed_at + holding_period {
+      amount += self.sealed_sol_reward;
+      self.sealed_sol_reward = 0;
+    }
+
+    if now >= self.deferred_at + holding_period {
+      amount += self.deferred_sol_reward;
+      self.deferred_sol_reward = 0;
+    }
+
+    self.seal_deferred_sol_reward();
+
+    if amount == 0 {
+      return err!(errors::SaleHandler::HoldingPeriodNotPassed);
+    }
+
+    self.set_sol_reward(amount)?;
+
+    Ok(amount)
+  }
+
+  // Seals the open deferred commissions with their timestamp once the sealed ones are settled
+  fn seal_deferred_sol_reward(
+    &mut self,
+  ) {
+    if self.sealed_sol_reward > 0 || self.deferred_sol_reward == 0 {
+      return;
+    }
+
+    self.sealed_sol_reward = self.deferred_sol_reward;
+    self.sealed_at = self.deferred_at;
+    self.deferred_sol_reward = 0;
+  }
+
   pub fn set_token_reward(
     &mut self,
     amount: u128,
   ) -> Result<()> {
-    self.token_reward += amount;
+    let offset = amount.min(self.token_debt);
+    self.token_debt -= offset;
+    self.token_reward += amount - offset;
 
     Ok(())
   }
 
-  pub fn get_interest(
+  // Returns the part of `amount` left after paying off the debt
+  pub fn repay_sol_debt(
+    &mut self,
+    amount: u64,
+  ) -> u64 {
+    let offset = amount.min(self.sol_debt);
+    self.sol_debt -= offset;
+
+    amount - offset
+  }
+
+  // Returns the parts taken from deferred and pending rewards
+  pub fn clawback_sol_reward(
+    &mut self,
+    amount: u64,
+  ) -> Result<(u64, u64)> {
+    let (deferred, pending) = clawback(amount, &mut self.deferred_sol_reward, &mut self.sealed_sol_reward, &mut self.sol_reward, &mut self.sol_debt);
+    self.sol_earned = self.sol_earned.saturating_sub(amount - deferred);
+
+    Ok((deferred, pending))
+  }
+
+  pub fn clawback_token_reward(
+    &mut self,
+    amount: u128,
+  ) -> Result<()> {
+    let unclaimed = self.token_reward.saturating_sub(self.token_reward_claimed);
+    let taken = amount.min(unclaimed);
+    self.token_reward -= taken;
+    self.token_debt += amount - taken;
+
+    Ok(())
+  }
+
+  pub fn set_token_reward_claimed(
+    &mut self,
+    amount: u128,
+  ) -> Result<()> {
+    self.token_reward_claimed += amount;
+
+    Ok(())
+  }
+
+  pub fn get_owner(
+    &self,
+  ) -> Pubkey {
+    self.owner
+  }
+
+  pub fn is_owner(
+    &self,
+    address: Pubkey,
+  ) -> bool {
+    self.owner != Pubkey::default() && self.owner == address
+  }
+
+  pub fn is_registered(
+    &self,
+  ) -> bool {
+    self.registered
+  }
+
+  pub fn is_self_referral(
+    &self,
+    address: Pubkey,
+  ) -> bool {
+    self.is_owner(address) || self.linked_wallets.contains(&address)
+  }
+
+  pub fn get_available_commission(
+    &self,
+    amount: u128,
+  ) -> u128 {
+    if self.commission_cap_usd == 0 {
+      return amount;
+    }
+
+    u128::min(amount, self.commission_cap_usd.saturating_sub(self.commission_usd))
+  }
+
+  pub fn is_signed_claims(
+    &self,
+  ) -> bool {
+    self.signed_claims
+  }
+
+  pub fn get_nonce(
+    &self,
+  ) -> u64 {
+    self.nonce
+  }
+
+  pub fn get_parent(
+    &self,
+  ) -> Pubkey {
+    self.parent
+  }
+
+  pub fn set_referred_volume(
+    &mut self,
+    amount: u128,
+  ) -> Result<()> {
+    self.referred_volume += amount;
+
+    Ok(())
+  }
+
+  pub fn set_referred_purchase(
+    &mut self,
+  ) -> Result<()> {
+    self.referred_purchases += 1;
+
+    Ok(())
+  }
+
+  pub fn set_referred_buyer(
+    &mut self,
+  ) -> Result<()> {
+    self.referred_buyers += 1;
+
+    Ok(())
+  }
+
+  pub fn set_sol_volume(
     &mut self,
+    usd_amount: u128,
+  ) -> Result<()> {
+    self.sol_volume_usd += usd_amount;
+
+    Ok(())
+  }
+
+  pub fn set_spl_volume(
+    &mut self,
+    usd_amount: u128,
+  ) -> Result<()> {
+    self.spl_volume_usd += usd_amount;
+
+    Ok(())
+  }
+
+  pub fn set_tier(
+    &mut self,
+    tier: u8,
+  ) -> Result<()> {
+    self.tier = tier;
+
+    Ok(())
+  }
+
+  pub fn get_referred_volume(
+    &self,
+  ) -> u128 {
+    self.referred_volume
+  }
+
+  pub fn get_tier(
+    &self,
+  ) -> u8 {
+    self.tier
+  }
+
+  pub fn get_interest(
+    &self,
   ) -> (u64, u64) {
     (self.main_interest, self.secondary_interest)
   }
 
+  pub fn get_buyer_bonus(
+    &self,
+  ) -> u64 {
+    self.buyer_bonus
+  }
+
   pub fn get_sol_reward(
     &mut self,
   ) -> u64 {
@@ -129,15 +549,61 @@ impl Partner {
     self.usdc_reward
   }
 
+  pub fn get_deferred_sol_reward(
+    &self,
+  ) -> u64 {
+    self.deferred_sol_reward + self.sealed_sol_reward
+  }
+
+  pub fn get_sol_debt(
+    &self,
+  ) -> u64 {
+    self.sol_debt
+  }
+
+  pub fn get_token_debt(
+    &self,
+  ) -> u128 {
+    self.token_debt
+  }
+
   pub fn get_token_reward(
     &mut self,
   ) -> u128 {
     self.token_reward
   }
 
+  pub fn get_token_reward_claimed(
+    &mut self,
+  ) -> u128 {
+    self.token_reward_claimed
+  }
+
+  pub fn is_enabled(
+    &self,
+  ) -> bool {
+    self.enabled
+  }
+
+  pub fn is_pending(
+    &self,
+  ) -> bool {
+    self.pending
+  }
+
+  pub fn set_pending(
+    &mut self,
+  ) -> Result<()> {
+    self.pending = true;
+    self.enabled = false;
+
+    Ok(())
+  }
+
   pub fn enable(
     &mut self,
   ) -> Result<()> {
+    self.pending = false;
     self.enabled = true;
 
     Ok(())
@@ -151,3 +617,76 @@ impl Partner {
     Ok(())
   }
 }
+
+// Partner layout of the first deployment, read once by `migrate_partner`
+#[derive(AnchorDeserialize)]
+pub struct LegacyPartner {
+  main_interest: u64,
+  secondary_interest: u64,
+
+  sol_reward: u64,
+  usdt_reward: u64,
+  usdc_reward: u64,
+  token_reward: u128,
+
+  enabled: bool,
+}
+
+impl LegacyPartner {
+  pub const MAX_SIZE: usize = (5 * 8) + 16 + 1 + 3;
+}
+
+/// Takes `amount` from deferred, newest first, then pending rewards, the rest becomes debt
+pub fn clawback(
+  amount: u64,
+  deferred: &mut u64,
+  sealed: &mut u64,
+  reward: &mut u64,
+  debt: &mut u64,
+) -> (u64, u64) {
+  let from_deferred = amount.min(*deferred);
+  *deferred -= from_deferred;
+
+  let from_sealed = (amount - from_deferred).min(*sealed);
+  *sealed -= from_sealed;
+
+  let from_reward = (amount - from_deferred - from_sealed).min(*reward);
+  *reward -= from_reward;
+
+  *debt += amount - from_deferred - from_sealed - from_reward;
+
+  (from_deferred + from_sealed, from_reward)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn takes_deferred_before_sealed() {
+    let (mut deferred, mut sealed, mut reward, mut debt) = (30, 50, 40, 0);
+    assert_eq!(clawback(60, &mut deferred, &mut sealed, &mut reward, &mut debt), (60, 0));
+    assert_eq!((deferred, sealed, reward, debt), (0, 20, 40, 0));
+  }
+
+  #[test]
+  fn takes_pending_once_deferred_is_empty() {
+    let (mut deferred, mut sealed, mut reward, mut debt) = (10, 20, 40, 0);
+    assert_eq!(clawback(50, &mut deferred, &mut sealed, &mut reward, &mut debt), (30, 20));
+    assert_eq!((deferred, sealed, reward, debt), (0, 0, 20, 0));
+  }
+
+  #[test]
+  fn claimed_part_becomes_debt() {
+    let (mut deferred, mut sealed, mut reward, mut debt) = (10, 0, 15, 5);
+    assert_eq!(clawback(40, &mut deferred, &mut sealed, &mut reward, &mut debt), (10, 15));
+    assert_eq!((deferred, sealed, reward, debt), (0, 0, 0, 20));
+  }
+
+  #[test]
+  fn zero_amount_changes_nothing() {
+    let (mut deferred, mut sealed, mut reward, mut debt) = (10, 20, 30, 40);
+    assert_eq!(clawback(0, &mut deferred, &mut sealed, &mut reward, &mut debt), (0, 0));
+    assert_eq!((deferred, sealed, reward, debt), (10, 20, 30, 40));
+  }
+}
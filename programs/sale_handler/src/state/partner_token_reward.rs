@@ -0,0 +1,151 @@
+use anchor_lang::prelude::*;
+use crate::errors;
+use crate::state::partner::clawback;
+
+// Commissions of a partner in one accepted payment token
+#[account]
+pub struct PartnerTokenReward {
+  reward: u64,
+  deferred_reward: u64,
+  deferred_at: i64,
+  // Older deferred commissions, set aside so newer ones cannot postpone them
+  sealed_reward: u64,
+  sealed_at: i64,
+  earned: u64,
+  claimed: u64,
+  swept: u64,
+  // Clawed back commissions already claimed, offset against future earnings
+  debt: u64,
+  // USD value of commissioned purchases paid in this token, never reset
+  volume_usd: u128,
+}
+
+impl PartnerTokenReward {
+  pub const MAX_SIZE: usize = (9 * 8) + 16;
+
+  pub fn set_reward(
+    &mut self,
+    amount: u64,
+  ) -> Result<()> {
+    self.reward += amount;
+    self.earned += amount;
+
+    Ok(())
+  }
+
+  pub fn reset_reward(
+    &mut self,
+  ) -> Result<()> {
+    self.claimed += self.reward;
+    self.reward = 0;
+
+    Ok(())
+  }
+
+  pub fn sweep_reward(
+    &mut self,
+  ) -> Result<()> {
+    self.swept += self.reward;
+    self.reward = 0;
+
+    Ok(())
+  }
+
+  pub fn set_volume(
+    &mut self,
+    usd_amount: u128,
+  ) -> Result<()> {
+    self.volume_usd += usd_amount;
+
+    Ok(())
+  }
+
+  pub fn set_deferred_reward(
+    &mut self,
+    amount: u64,
+    now: i64,
+  ) -> Result<()> {
+    if amount > 0 {
+      self.seal_deferred_reward();
+      self.deferred_reward += amount;
+      self.deferred_at = now;
+    }
+
+    Ok(())
+  }
+
+  /// Takes deferred commissions older than `holding_period` out of escrow accounting, returns the amount to transfer
+  pub fn settle_deferred_reward(
+    &mut self,
+    now: i64,
+    holding_period: i64,
+  ) -> Result<u64> {
+    if self.get_deferred_reward() == 0 {
+      return err!(errors::SaleHandler::PartnerNoFunds);
+    }
+
+    let mut amount = 0;
+    if now >= self.sealed_at + holding_period {
+      amount += self.sealed_reward;
+      self.sealed_reward = 0;
+    }
+
+    if now >= self.deferred_at + holding_period {
+      amount += self.deferred_reward;
+      self.deferred_reward = 0;
+    }
+
+    self.seal_deferred_reward();
+
+    if amount == 0 {
+      return err!(errors::SaleHandler::HoldingPeriodNotPassed);
+    }
+
+    Ok(amount)
+  }
+
+  // Seals the open deferred commissions with their timestamp once the sealed ones are settled
+  fn seal_deferred_reward(
+    &mut self,
+  ) {
+    if self.sealed_reward > 0 || self.deferred_reward == 0 {
+      return;
+    }
+
+    self.sealed_reward = self.deferred_reward;
+    self.sealed_at = self.deferred_at;
+    self.deferred_reward = 0;
+  }
+
+  pub fn repay_debt(
+    &mut self,
+    amount: u64,
+  ) -> u64 {
+    let offset = amount.min(self.debt);
+    self.debt -= offset;
+
+    amount - offset
+  }
+
+  pub fn clawback_reward(
+    &mut self,
+    amount: u64,
+  ) -> Result<(u64, u64)> {
+    let (deferred, pending) = clawback(amount, &mut self.deferred_reward, &mut self.sealed_reward, &mut self.reward, &mut self.debt);
+    self.earned = self.earned.saturating_sub(amount - deferred);
+
+    Ok((deferred, pending))
+  }
+
+  pub fn get_reward(
+    &self,
+  ) -> u64 {
+    self.reward
+  }
+
+  pub fn get_deferred_reward(
+    &self,
+  ) -> u64 {
+    self.deferred_reward + self.sealed_reward
+  }
+}
@@ -0,0 +1,116 @@
+use anchor_lang::prelude::*;
+use crate::errors;
+use crate::config::PRECISION;
+
+#[derive(Clone, AnchorDeserialize, AnchorSerialize)]
+pub struct PromoCodeParams {
+  pub bonus_percent: u64,
+  pub start_time: i64,
+  pub end_time: i64,
+  pub max_redemptions: u64,
+  pub max_wallet_redemptions: u64,
+  // -1 when redeemable on every step
+  pub step: i16,
+}
+
+#[account]
+pub struct PromoCode {
+  bonus_percent: u64,
+  start_time: i64,
+  end_time: i64,
+  max_redemptions: u64,
+  max_wallet_redemptions: u64,
+  redemptions: u64,
+  // -1 when redeemable on every step
+  step: i16,
+  enabled: bool,
+}
+
+impl PromoCode {
+  pub const MAX_SIZE: usize = (6 * 8) + 2 + 1 + 3;
+
+  pub fn init(
+    &mut self,
+    params: PromoCodeParams,
+  ) -> Result<()> {
+    if params.bonus_percent == 0 || params.bonus_percent > 10u64.pow(PRECISION) || params.start_time >= params.end_time {
+      return err!(errors::SaleHandler::WrongPromoCodeValues);
+    }
+
+    self.bonus_percent = params.bonus_percent;
+    self.start_time = params.start_time;
+    self.end_time = params.end_time;
+    self.max_redemptions = params.max_redemptions;
+    self.max_wallet_redemptions = params.max_wallet_redemptions;
+    self.redemptions = 0;
+    self.step = params.step;
+    self.enabled = true;
+
+    Ok(())
+  }
+
+  pub fn disable(
+    &mut self,
+  ) -> Result<()> {
+    self.enabled = false;
+
+    Ok(())
+  }
+
+  pub fn redeem(
+    &mut self,
+    step: i16,
+    now: i64,
+  ) -> Result<()> {
+    if !self.enabled || now < self.start_time || now >= self.end_time {
+      return err!(errors::SaleHandler::PromoCodeNotActive);
+    }
+
+    if self.step != -1 && self.step != step {
+      return err!(errors::SaleHandler::PromoCodeWrongStep);
+    }
+
+    if self.redemptions >= self.max_redemptions {
+      return err!(errors::SaleHandler::PromoCodeRedemptionsExceeded);
+    }
+
+    self.redemptions += 1;
+
+    Ok(())
+  }
+
+  pub fn get_max_wallet_redemptions(
+    &self,
+  ) -> u64 {
+    self.max_wallet_redemptions
+  }
+
+  pub fn calculate_bonus(
+    &self,
+    token_amount: u128,
+  ) -> u128 {
+    token_amount * u128::from(self.bonus_percent) / 10u128.pow(PRECISION)
+  }
+}
+
+#[account]
+pub struct PromoRedemption {
+  redemptions: u64,
+}
+
+impl PromoRedemption {
+  pub const MAX_SIZE: usize = 8 + 1;
+
+  pub fn redeem(
+    &mut self,
+    max_redemptions: u64,
+  ) -> Result<()> {
+    if self.redemptions >= max_redemptions {
+      return err!(errors::SaleHandler::PromoCodeWalletRedemptionsExceeded);
+    }
+
+    self.redemptions += 1;
+
+    Ok(())
+  }
+}
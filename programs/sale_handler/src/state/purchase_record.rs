@@ -0,0 +1,130 @@
+use anchor_lang::prelude::*;
+use crate::errors;
+use crate::config::MAX_REFERRAL_LEVELS;
+
+// Commissions of one referred purchase, kept so a refund can reverse every level
+#[account]
+pub struct PurchaseRecord {
+  purchaser: Pubkey,
+  // Default for SOL purchases
+  mint: Pubkey,
+  // Direct partner first, then the paid ancestors
+  partners: Vec<Pubkey>,
+  levels: Vec<u8>,
+  rewards: Vec<u64>,
+  token_reward: u128,
+  // Paid through escrow, the settlement transfer of a fee mint charged its fee again
+  deferred: bool,
+  clawed_back: bool,
+}
+
+impl PurchaseRecord {
+  pub const MAX_SIZE: usize = 32 + 32 + (4 + 32 * (MAX_REFERRAL_LEVELS + 1)) + (4 + MAX_REFERRAL_LEVELS + 1) + (4 + 8 * (MAX_REFERRAL_LEVELS + 1)) + 16 + 1 + 1;
+
+  pub fn init(
+    &mut self,
+    purchaser: Pubkey,
+    mint: Pubkey,
+    token_reward: u128,
+    deferred: bool,
+  ) -> Result<()> {
+    self.purchaser = purchaser;
+    self.mint = mint;
+    self.partners = Vec::new();
+    self.levels = Vec::new();
+    self.rewards = Vec::new();
+    self.token_reward = token_reward;
+    self.deferred = deferred;
+    self.clawed_back = false;
+
+    Ok(())
+  }
+
+  pub fn add_reward(
+    &mut self,
+    partner: Pubkey,
+    level: u8,
+    reward: u64,
+  ) -> Result<()> {
+    self.partners.push(partner);
+    self.levels.push(level);
+    self.rewards.push(reward);
+
+    Ok(())
+  }
+
+  pub fn set_clawed_back(
+    &mut self,
+  ) -> Result<()> {
+    if self.clawed_back {
+      return err!(errors::SaleHandler::PurchaseClawedBack);
+    }
+
+    self.clawed_back = true;
+
+    Ok(())
+  }
+
+  pub fn get_mint(
+    &self,
+  ) -> Pubkey {
+    self.mint
+  }
+
+  pub fn get_token_reward(
+    &self,
+  ) -> u128 {
+    self.token_reward
+  }
+
+  pub fn is_deferred(
+    &self,
+  ) -> bool {
+    self.deferred
+  }
+
+  /// Partner, level and commission of every paid level, the direct partner first
+  pub fn get_rewards(
+    &self,
+  ) -> impl Iterator<Item = (Pubkey, u8, u64)> + '_ {
+    self.partners.iter().zip(self.levels.iter()).zip(self.rewards.iter())
+      .map(|((partner, level), reward)| (*partner, *level, *reward))
+  }
+
+  pub fn get_levels_count(
+    &self,
+  ) -> usize {
+    self.partners.len()
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn purchase_record() -> PurchaseRecord {
+    let mut purchase_record = PurchaseRecord::deserialize(&mut &vec![0u8; PurchaseRecord::MAX_SIZE][..]).unwrap();
+    purchase_record.init(Pubkey::new_unique(), Pubkey::default(), 7, false).unwrap();
+    purchase_record
+  }
+
+  #[test]
+  fn keeps_levels_in_order() {
+    let (partner, ancestor) = (Pubkey::new_unique(), Pubkey::new_unique());
+    let mut purchase_record = purchase_record();
+    purchase_record.add_reward(partner, 0, 100).unwrap();
+    purchase_record.add_reward(ancestor, 2, 20).unwrap();
+
+    assert_eq!(purchase_record.get_levels_count(), 2);
+    assert_eq!(purchase_record.get_rewards().collect::<Vec<_>>(), vec![(partner, 0, 100), (ancestor, 2, 20)]);
+  }
+
+  #[test]
+  fn rejects_second_clawback() {
+    let mut purchase_record = purchase_record();
+    assert!(purchase_record.set_clawed_back().is_ok());
+
+    let err = purchase_record.set_clawed_back().unwrap_err();
+    assert_eq!(err, errors::SaleHandler::PurchaseClawedBack.into());
+  }
+}
@@ -1,17 +1,24 @@
 use anchor_lang::prelude::*;
+use crate::config::PARTNER_CODE_MAX_LEN;
 
 #[account]
 pub struct Purchaser {
   purchased: u128,
+  // Partner of the first referred purchase
+  partner_code: String,
+  // Purchases made, numbers the purchase records
+  purchases: u64,
 }
 
 impl Purchaser {
-  pub const MAX_SIZE: usize = 16 + 1;
+  pub const MAX_SIZE: usize = 16 + (4 + PARTNER_CODE_MAX_LEN) + 8 + 1;
 
   pub fn init(
     &mut self,
   ) -> Result<()> {
     self.purchased = 0;
+    self.partner_code = String::new();
+    self.purchases = 0;
 
     Ok(())
   }
@@ -25,6 +32,53 @@ impl Purchaser {
     Ok(())
   }
 
+  pub fn set_partner_code(
+    &mut self,
+    partner_code: String,
+  ) -> Result<()> {
+    self.partner_code = partner_code;
+
+    Ok(())
+  }
+
+  pub fn get_partner_code(
+    &self,
+  ) -> &str {
+    &self.partner_code
+  }
+
+  /// Code the purchase is attributed to, the bound one taking precedence over `partner_code`
+  pub fn get_referral_code<'a>(
+    &'a self,
+    partner_code: &'a str,
+  ) -> &'a str {
+    if self.is_partner_bound() {
+      return &self.partner_code;
+    }
+
+    partner_code
+  }
+
+  pub fn is_partner_bound(
+    &self,
+  ) -> bool {
+    !self.partner_code.is_empty()
+  }
+
+  pub fn set_purchases(
+    &mut self,
+  ) -> Result<()> {
+    self.purchases += 1;
+
+    Ok(())
+  }
+
+  pub fn get_purchases(
+    &self,
+  ) -> u64 {
+    self.purchases
+  }
+
   pub fn get_purchased(
     &mut self,
   ) -> u128 {
@@ -1,6 +1,6 @@
 use anchor_lang::prelude::*;
 use crate::errors;
-use crate::config::{ MAIN_INTEREST, MAX_CAP, MIN_CAP, PRECISION, SECONDARY_INTEREST };
+use crate::config::{ REFERRAL_BUDGET, MAIN_INTEREST, MAX_CAP, MIN_CAP, PRECISION, SECONDARY_INTEREST, MAX_REFERRAL_LEVELS, MAX_PARTNER_TIERS };
 
 #[derive(Clone, PartialEq, AnchorDeserialize, AnchorSerialize)]
 pub enum Status {
@@ -9,6 +9,38 @@ pub enum Status {
   Disabled,
 }
 
+#[derive(Clone, Copy, PartialEq, AnchorDeserialize, AnchorSerialize)]
+pub enum DisabledPartnerPurchasePolicy {
+  NoCommission,
+  Reject,
+}
+
+#[derive(Clone, Copy, PartialEq, AnchorDeserialize, AnchorSerialize)]
+pub enum DisabledPartnerClaimPolicy {
+  EarnedBeforeDisable,
+  Block,
+}
+
+#[derive(Clone, Copy, PartialEq, AnchorDeserialize, AnchorSerialize)]
+pub enum SelfReferralPolicy {
+  Reject,
+  NoCommission,
+}
+
+#[derive(Clone, Copy, PartialEq, AnchorDeserialize, AnchorSerialize)]
+pub enum PartnerRegistration {
+  Closed,
+  Open,
+  Approval,
+  Invitation,
+}
+
+#[derive(Clone, Copy, PartialEq, AnchorDeserialize, AnchorSerialize)]
+pub enum SettlementMode {
+  Immediate,
+  Deferred,
+}
+
 #[account]
 pub struct SaleHandler {
   max_cap: u64,
@@ -22,10 +54,36 @@ pub struct SaleHandler {
   // NOTE: unforturantelly unable to use array of objects
   bonus_percents: Vec<u64>,
   bonus_thresholds: Vec<u64>,
+  token_mint: Pubkey,
+  tge_timestamp: i64,
+  tge_percent: u64,
+  vesting_period: i64,
+  disabled_partner_purchase_policy: DisabledPartnerPurchasePolicy,
+  disabled_partner_claim_policy: DisabledPartnerClaimPolicy,
+  self_referral_policy: SelfReferralPolicy,
+  partner_registration: PartnerRegistration,
+  // Commission of the parent, grandparent, ... of the direct partner
+  referral_interests: Vec<u64>,
+  // Tier `n` is reached once referred USD volume crosses `tier_thresholds[n - 1]`
+  tier_thresholds: Vec<u64>,
+  tier_main_interests: Vec<u64>,
+  tier_secondary_interests: Vec<u64>,
+  // Max of partner main interest, ancestor interests and buyer bonus combined
+  referral_budget: u64,
+  // Deferred commissions are held in escrow for `holding_period` seconds before settlement
+  settlement_mode: SettlementMode,
+  holding_period: i64,
+  // Rewards left unclaimed `claim_period` seconds after finalization can be swept, 0 disables expiry
+  finalized_at: i64,
+  claim_period: i64,
+  // Pegged tokens are priced at the oracle rate once it deviates from the peg by more than `depeg_threshold`
+  // of it and purchases are rejected beyond `depeg_limit`, 0 limit disables the guard
+  depeg_threshold: u64,
+  depeg_limit: u64,
 }
 
 impl SaleHandler {
-  pub const MAX_SIZE: usize = (4 * 8) + 16 + 2 + 1 + 2 + 1 + 2 * (8 * 10 + 24);
+  pub const MAX_SIZE: usize = (4 * 8) + 16 + 2 + 1 + 2 + 1 + 2 * (8 * 10 + 24) + 32 + (3 * 8) + 3 + 1 + (4 + 8 * MAX_REFERRAL_LEVELS) + 3 * (4 + 8 * MAX_PARTNER_TIERS) + 8 + 1 + 8 + (2 * 8) + (2 * 8);
 
   pub fn init(
     &mut self,
@@ -42,6 +100,32 @@ impl SaleHandler {
     self.bonus_thresholds = Vec::new();
     self.bonus_percents = Vec::new();
 
+    self.token_mint = Pubkey::default();
+    self.tge_timestamp = 0;
+    self.tge_percent = 0;
+    self.vesting_period = 0;
+
+    self.disabled_partner_purchase_policy = DisabledPartnerPurchasePolicy::NoCommission;
+    self.disabled_partner_claim_policy = DisabledPartnerClaimPolicy::EarnedBeforeDisable;
+    self.self_referral_policy = SelfReferralPolicy::Reject;
+    self.partner_registration = PartnerRegistration::Closed;
+    self.referral_interests = Vec::new();
+
+    self.tier_thresholds = Vec::new();
+    self.tier_main_interests = Vec::new();
+    self.tier_secondary_interests = Vec::new();
+
+    self.referral_budget = REFERRAL_BUDGET;
+
+    self.settlement_mode = SettlementMode::Immediate;
+    self.holding_period = 0;
+
+    self.finalized_at = 0;
+    self.claim_period = 0;
+
+    self.depeg_threshold = 0;
+    self.depeg_limit = 0;
+
     Ok(())
   }
 
@@ -106,6 +190,194 @@ impl SaleHandler {
     Ok(())
   }
 
+  pub fn set_tge(
+    &mut self,
+    token_mint: Pubkey,
+    tge_timestamp: i64,
+    tge_percent: u64,
+    vesting_period: i64,
+  ) -> Result<()> {
+    if tge_percent > 10u64.pow(PRECISION) {
+      return err!(errors::SaleHandler::SaleHandlerTgePercentTooLarge);
+    }
+
+    if vesting_period < 0 {
+      return err!(errors::SaleHandler::SaleHandlerWrongVestingPeriod);
+    }
+
+    self.token_mint = token_mint;
+    self.tge_timestamp = tge_timestamp;
+    self.tge_percent = tge_percent;
+    self.vesting_period = vesting_period;
+
+    Ok(())
+  }
+
+  pub fn set_partner_policy(
+    &mut self,
+    purchase_policy: DisabledPartnerPurchasePolicy,
+    claim_policy: DisabledPartnerClaimPolicy,
+  ) -> Result<()> {
+    self.disabled_partner_purchase_policy = purchase_policy;
+    self.disabled_partner_claim_policy = claim_policy;
+
+    Ok(())
+  }
+
+  pub fn set_self_referral_policy(
+    &mut self,
+    self_referral_policy: SelfReferralPolicy,
+  ) -> Result<()> {
+    self.self_referral_policy = self_referral_policy;
+
+    Ok(())
+  }
+
+  pub fn set_settlement(
+    &mut self,
+    settlement_mode: SettlementMode,
+    holding_period: i64,
+  ) -> Result<()> {
+    if holding_period < 0 {
+      return err!(errors::SaleHandler::WrongHoldingPeriod);
+    }
+
+    self.settlement_mode = settlement_mode;
+    self.holding_period = holding_period;
+
+    Ok(())
+  }
+
+  pub fn set_claim_period(
+    &mut self,
+    claim_period: i64,
+  ) -> Result<()> {
+    if claim_period < 0 {
+      return err!(errors::SaleHandler::WrongClaimPeriod);
+    }
+
+    self.claim_period = claim_period;
+
+    Ok(())
+  }
+
+  pub fn set_depeg_guard(
+    &mut self,
+    depeg_threshold: u64,
+    depeg_limit: u64,
+  ) -> Result<()> {
+    if depeg_threshold > depeg_limit || u128::from(depeg_limit) >= 10u128.pow(PRECISION) {
+      return err!(errors::SaleHandler::WrongDepegGuard);
+    }
+
+    self.depeg_threshold = depeg_threshold;
+    self.depeg_limit = depeg_limit;
+
+    Ok(())
+  }
+
+  pub fn set_partner_registration(
+    &mut self,
+    partner_registration: PartnerRegistration,
+  ) -> Result<()> {
+    self.partner_registration = partner_registration;
+
+    Ok(())
+  }
+
+  pub fn set_referral_interests(
+    &mut self,
+    interests: Vec<u64>,
+  ) -> Result<()> {
+    if interests.len() > MAX_REFERRAL_LEVELS {
+      return err!(errors::SaleHandler::WrongReferralInterests);
+    }
+
+    let total = interests.iter().fold(self.main_interest, |total, interest| total + interest);
+    if total > 10u64.pow(PRECISION) {
+      return err!(errors::SaleHandler::WrongReferralInterests);
+    }
+
+    if interests.iter().sum::<u64>() > self.referral_budget {
+      return err!(errors::SaleHandler::WrongReferralInterests);
+    }
+
+    self.referral_interests = interests;
+
+    Ok(())
+  }
+
+  pub fn set_partner_tiers(
+    &mut self,
+    thresholds: Vec<u64>,
+    main_interests: Vec<u64>,
+    secondary_interests: Vec<u64>,
+  ) -> Result<()> {
+    if thresholds.len() != main_interests.len() || thresholds.len() != secondary_interests.len() || thresholds.len() > MAX_PARTNER_TIERS {
+      return err!(errors::SaleHandler::WrongPartnerTiersLens);
+    }
+
+    for idx in 0..thresholds.len() {
+      if main_interests[idx] > 1000_000_000 || secondary_interests[idx] > 1000_000_000 {
+        return err!(errors::SaleHandler::WrongPartnerTiersValues);
+      }
+
+      if idx == 0 {
+        if thresholds[0] == 0 {
+          return err!(errors::SaleHandler::WrongPartnerTiersValues);
+        }
+      } else {
+        if thresholds[idx -1] >= thresholds[idx] {
+          return err!(errors::SaleHandler::WrongPartnerTiersValues);
+        }
+      }
+    }
+
+    self.tier_thresholds = thresholds;
+    self.tier_main_interests = main_interests;
+    self.tier_secondary_interests = secondary_interests;
+
+    Ok(())
+  }
+
+  /// Carries the first deployment fields over, appended ones take their `init` defaults
+  pub fn migrate(
+    &mut self,
+    legacy: &LegacySaleHandler,
+  ) -> Result<()> {
+    self.init().unwrap();
+
+    self.max_cap = legacy.max_cap;
+    self.min_cap = legacy.min_cap;
+    self.main_interest = legacy.main_interest;
+    self.secondary_interest = legacy.secondary_interest;
+    self.total_sold = legacy.total_sold;
+    self.step = legacy.step;
+    self.status = legacy.status.clone();
+    self.enabled = legacy.enabled;
+    self.bonus_percents = legacy.bonus_percents.clone();
+    self.bonus_thresholds = legacy.bonus_thresholds.clone();
+
+    Ok(())
+  }
+
+  pub fn set_referral_budget(
+    &mut self,
+    referral_budget: u64,
+  ) -> Result<()> {
+    if referral_budget > 1000_000_000 {
+      return err!(errors::SaleHandler::SaleHandlerReferralBudgetTooLarge);
+    }
+
+    if referral_budget < self.get_referral_interests_total() {
+      return err!(errors::SaleHandler::SaleHandlerReferralBudgetTooSmall);
+    }
+
+    self.referral_budget = referral_budget;
+
+    Ok(())
+  }
+
   pub fn set_enable(
     &mut self,
   ) -> Result<()> {
@@ -120,12 +392,14 @@ impl SaleHandler {
 
   pub fn set_disable(
     &mut self,
+    now: i64,
   ) -> Result<()> {
     if self.status != Status::Enabled {
       return err!(errors::SaleHandler::SaleHandlerDisabled);
     }
 
     self.status = Status::Disabled;
+    self.finalized_at = now;
 
     Ok(())
   }
@@ -173,17 +447,157 @@ impl SaleHandler {
   }
 
   pub fn get_interest(
-    &mut self,
+    &self,
   ) -> (u64, u64) {
     (self.main_interest, self.secondary_interest)
   }
 
+  pub fn get_token_mint(
+    &self,
+  ) -> Pubkey {
+    self.token_mint
+  }
+
+  pub fn get_disabled_partner_purchase_policy(
+    &self,
+  ) -> DisabledPartnerPurchasePolicy {
+    self.disabled_partner_purchase_policy
+  }
+
+  pub fn get_disabled_partner_claim_policy(
+    &self,
+  ) -> DisabledPartnerClaimPolicy {
+    self.disabled_partner_claim_policy
+  }
+
+  pub fn get_self_referral_policy(
+    &self,
+  ) -> SelfReferralPolicy {
+    self.self_referral_policy
+  }
+
+  pub fn get_partner_registration(
+    &self,
+  ) -> PartnerRegistration {
+    self.partner_registration
+  }
+
+  pub fn get_referral_interests(
+    &self,
+  ) -> &Vec<u64> {
+    &self.referral_interests
+  }
+
+  pub fn get_tier_interest(
+    &self,
+    tier: u8,
+  ) -> (u64, u64) {
+    let tier = usize::from(tier);
+    if tier == 0 || tier > self.tier_thresholds.len() {
+      return (0, 0);
+    }
+
+    (self.tier_main_interests[tier - 1], self.tier_secondary_interests[tier - 1])
+  }
+
+  pub fn calculate_tier(
+    &self,
+    volume: u128,
+  ) -> u8 {
+    let mut tier: u8 = 0;
+
+    for idx in 0..self.tier_thresholds.len() {
+      if volume >= self.tier_thresholds[idx].into() {
+        tier = (idx + 1) as u8;
+      } else {
+        break;
+      }
+    }
+
+    tier
+  }
+
+  pub fn get_referral_interests_total(
+    &self,
+  ) -> u64 {
+    self.referral_interests.iter().sum()
+  }
+
+  pub fn get_referral_budget(
+    &self,
+  ) -> u64 {
+    self.referral_budget
+  }
+
+  pub fn get_holding_period(
+    &self,
+  ) -> i64 {
+    self.holding_period
+  }
+
+  pub fn get_depeg_threshold(
+    &self,
+  ) -> u64 {
+    self.depeg_threshold
+  }
+
+  pub fn get_depeg_limit(
+    &self,
+  ) -> u64 {
+    self.depeg_limit
+  }
+
+  pub fn is_depeg_guard_enabled(
+    &self,
+  ) -> bool {
+    self.depeg_limit > 0
+  }
+
   pub fn is_enabled(
     &self,
   ) -> bool {
     self.status == Status::Enabled
   }
 
+  pub fn is_claim_expired(
+    &self,
+    now: i64,
+  ) -> bool {
+    self.claim_period > 0 && self.status == Status::Disabled && now >= self.finalized_at + self.claim_period
+  }
+
+  pub fn is_deferred_settlement(
+    &self,
+  ) -> bool {
+    self.settlement_mode == SettlementMode::Deferred
+  }
+
+  pub fn is_tge_reached(
+    &self,
+    now: i64,
+  ) -> bool {
+    self.token_mint != Pubkey::default() && self.tge_timestamp > 0 && now >= self.tge_timestamp
+  }
+
+  // Part of `total` unlocked at `now`: `tge_percent` at TGE, the rest linearly over `vesting_period`
+  pub fn calculate_unlocked(
+    &self,
+    total: u128,
+    now: i64,
+  ) -> u128 {
+    if !self.is_tge_reached(now) {
+      return 0;
+    }
+
+    let tge_amount = total * u128::from(self.tge_percent) / 10u128.pow(PRECISION);
+    let elapsed = now - self.tge_timestamp;
+    if self.vesting_period == 0 || elapsed >= self.vesting_period {
+      return total;
+    }
+
+    tge_amount + (total - tge_amount) * elapsed as u128 / self.vesting_period as u128
+  }
+
   pub fn calculate_bonus(
     &mut self,
     usd_amount: u128,
@@ -210,3 +624,22 @@ impl SaleHandler {
     return token_amount * self.bonus_percents[target as usize] as u128 / 10u128.pow(PRECISION);
   }
 }
+
+// Sale handler layout of the first deployment, read once by `migrate_sale_handler`
+#[derive(AnchorDeserialize)]
+pub struct LegacySaleHandler {
+  max_cap: u64,
+  min_cap: u64,
+  main_interest: u64,
+  secondary_interest: u64,
+  total_sold: u128,
+  step: i16,
+  status: Status,
+  enabled: bool,
+  bonus_percents: Vec<u64>,
+  bonus_thresholds: Vec<u64>,
+}
+
+impl LegacySaleHandler {
+  pub const MAX_SIZE: usize = (4 * 8) + 16 + 2 + 1 + 2 + 1 + 2 * (8 * 10 + 24);
+}
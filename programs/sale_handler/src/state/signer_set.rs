@@ -0,0 +1,100 @@
+use anchor_lang::prelude::*;
+use crate::errors;
+
+pub const MAX_SIGNERS: usize = 5;
+
+#[account]
+pub struct SignerSet {
+  // Parallel vectors, one entry per signer
+  keys: Vec<Pubkey>,
+  active_from: Vec<i64>,
+  active_until: Vec<i64>,
+}
+
+impl SignerSet {
+  pub const MAX_SIZE: usize = (4 + 32 * MAX_SIGNERS) + 2 * (4 + 8 * MAX_SIGNERS);
+
+  pub fn init(
+    &mut self,
+    key: Pubkey,
+  ) -> Result<()> {
+    self.keys = vec![key];
+    self.active_from = vec![0];
+    self.active_until = vec![i64::MAX];
+
+    Ok(())
+  }
+
+  pub fn add_signer(
+    &mut self,
+    key: Pubkey,
+    active_from: i64,
+    now: i64,
+  ) -> Result<()> {
+    self.remove_expired(now);
+
+    if self.keys.contains(&key) {
+      return err!(errors::SaleHandler::SignerAlreadyExists);
+    }
+
+    if self.keys.len() >= MAX_SIGNERS {
+      return err!(errors::SaleHandler::SignerSetFull);
+    }
+
+    self.keys.push(key);
+    self.active_from.push(active_from);
+    self.active_until.push(i64::MAX);
+
+    Ok(())
+  }
+
+  pub fn retire_signer(
+    &mut self,
+    key: Pubkey,
+    active_until: i64,
+  ) -> Result<()> {
+    let idx = match self.keys.iter().position(|k| *k == key) {
+      Some(idx) => idx,
+      None => return err!(errors::SaleHandler::SignerNotFound),
+    };
+
+    if active_until < self.active_from[idx] {
+      return err!(errors::SaleHandler::WrongSignerValidity);
+    }
+
+    self.active_until[idx] = active_until;
+
+    Ok(())
+  }
+
+  pub fn get_valid_signers(
+    &self,
+    now: i64,
+  ) -> Vec<Pubkey> {
+    let mut signers = Vec::new();
+
+    for idx in 0..self.keys.len() {
+      if self.active_from[idx] <= now && now < self.active_until[idx] {
+        signers.push(self.keys[idx]);
+      }
+    }
+
+    signers
+  }
+
+  fn remove_expired(
+    &mut self,
+    now: i64,
+  ) {
+    let mut idx = 0;
+    while idx < self.keys.len() {
+      if self.active_until[idx] <= now {
+        self.keys.remove(idx);
+        self.active_from.remove(idx);
+        self.active_until.remove(idx);
+      } else {
+        idx += 1;
+      }
+    }
+  }
+}
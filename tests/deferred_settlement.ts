@@ -0,0 +1,64 @@
+import { Keypair, PublicKey } from "@solana/web3.js";
+import { getAssociatedTokenAddressSync } from "@solana/spl-token";
+import { expect } from "chai";
+import {
+  AMOUNT,
+  COMMISSION,
+  airdrop,
+  balance,
+  createPaymentToken,
+  escrow,
+  fund,
+  initEscrow,
+  initPartner,
+  initSaleHandler,
+  partnerPda,
+  partnerRewardPda,
+  program,
+  purchase,
+  setSettlement,
+  settle,
+} from "./utils";
+
+describe("deferred settlement", () => {
+  const code = "deferred01";
+  const buyer = Keypair.generate();
+  const partnerOwner = Keypair.generate();
+  const partner = partnerPda(code);
+
+  let mint: PublicKey;
+
+  before(async () => {
+    await airdrop(buyer);
+    await initSaleHandler();
+    await initEscrow();
+    await setSettlement(true);
+
+    mint = await createPaymentToken();
+    await fund(mint, buyer, AMOUNT);
+    await initPartner(code, partnerOwner.publicKey);
+  });
+
+  after(() => setSettlement(false));
+
+  it("holds deferred commissions in escrow until settlement", async () => {
+    const escrowAta = getAssociatedTokenAddressSync(mint, escrow, true);
+    const partnerPdaAta = getAssociatedTokenAddressSync(mint, partner, true);
+    const partnerReward = partnerRewardPda(partner, mint);
+
+    await purchase(buyer, code, mint, 0, true);
+
+    expect(await balance(escrowAta)).to.equal(COMMISSION);
+    let reward = await program.account.partnerTokenReward.fetch(partnerReward);
+    expect(reward.deferredReward.toNumber()).to.equal(COMMISSION);
+    expect(reward.reward.toNumber()).to.equal(0);
+
+    await settle(code, mint);
+
+    expect(await balance(escrowAta)).to.equal(0);
+    expect(await balance(partnerPdaAta)).to.equal(COMMISSION);
+    reward = await program.account.partnerTokenReward.fetch(partnerReward);
+    expect(reward.deferredReward.toNumber()).to.equal(0);
+    expect(reward.reward.toNumber()).to.equal(COMMISSION);
+  });
+});
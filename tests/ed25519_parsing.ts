@@ -0,0 +1,102 @@
+import { BN } from "@coral-xyz/anchor";
+import { ComputeBudgetProgram, Keypair, PublicKey } from "@solana/web3.js";
+import {
+  AMOUNT,
+  airdrop,
+  claim,
+  createPaymentToken,
+  expectError,
+  fund,
+  initPartner,
+  initSaleHandler,
+  initSignerSet,
+  partnerPda,
+  program,
+  purchase,
+  setSettlement,
+  signClaim,
+  signerSet,
+} from "./utils";
+
+describe("ed25519 parsing", () => {
+  const code = "parsing01";
+  const buyer = Keypair.generate();
+  const partnerOwner = Keypair.generate();
+  const claimer = Keypair.generate();
+  const backend = Keypair.generate();
+
+  let mint: PublicKey;
+  let claimerAta: PublicKey;
+
+  before(async () => {
+    await airdrop(buyer, claimer);
+    await initSaleHandler();
+    await setSettlement(false);
+    await initSignerSet();
+    await program.methods
+      .addSigner(backend.publicKey, new BN(0))
+      .accountsPartial({ signerSet })
+      .rpc();
+
+    mint = await createPaymentToken();
+    await fund(mint, buyer, AMOUNT);
+    claimerAta = await fund(mint, claimer, 0);
+
+    await initPartner(code, partnerOwner.publicKey);
+    await program.methods
+      .setPartnerSignedClaims(true)
+      .accountsPartial({ partner: partnerPda(code) })
+      .rpc();
+    await purchase(buyer, code, mint, 0, false);
+  });
+
+  it("rejects a signature index pointing at another program", async () => {
+    const { sig, deadline } = await signClaim(
+      backend,
+      mint,
+      code,
+      claimer.publicKey
+    );
+
+    await expectError(
+      claim(code, mint, claimer, claimerAta, { sig, deadline })
+        .preInstructions([
+          ComputeBudgetProgram.setComputeUnitLimit({ units: 400_000 }),
+        ])
+        .rpc(),
+      "SignatureVerificationFailedWrongProgram"
+    );
+  });
+
+  it("rejects a signature index that does not precede the claim", async () => {
+    const { sig, deadline, ix } = await signClaim(
+      backend,
+      mint,
+      code,
+      claimer.publicKey
+    );
+
+    await expectError(
+      claim(code, mint, claimer, claimerAta, { sig, deadline, idx: 1 })
+        .preInstructions([ix])
+        .rpc(),
+      "SignatureVerificationFailedWrongIndex"
+    );
+  });
+
+  it("rejects an Ed25519 instruction signed by an unknown key", async () => {
+    const { sig, deadline, ix } = await signClaim(
+      Keypair.generate(),
+      mint,
+      code,
+      claimer.publicKey
+    );
+
+    await expectError(
+      claim(code, mint, claimer, claimerAta, { sig, deadline })
+        .preInstructions([ix])
+        .rpc(),
+      "SignatureVerificationFailedNoMatch"
+    );
+  });
+});
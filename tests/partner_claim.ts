@@ -0,0 +1,68 @@
+import { Keypair, PublicKey } from "@solana/web3.js";
+import { getAssociatedTokenAddressSync } from "@solana/spl-token";
+import { expect } from "chai";
+import {
+  AMOUNT,
+  COMMISSION,
+  airdrop,
+  balance,
+  claim,
+  createPaymentToken,
+  expectError,
+  fund,
+  initPartner,
+  initSaleHandler,
+  partnerPda,
+  partnerRewardPda,
+  program,
+  purchase,
+  setSettlement,
+} from "./utils";
+
+describe("partner claim", () => {
+  const code = "claim01";
+  const buyer = Keypair.generate();
+  const partnerOwner = Keypair.generate();
+  const stranger = Keypair.generate();
+  const partner = partnerPda(code);
+
+  let mint: PublicKey;
+  let partnerOwnerAta: PublicKey;
+  let strangerAta: PublicKey;
+
+  before(async () => {
+    await airdrop(buyer, partnerOwner, stranger);
+    await initSaleHandler();
+    await setSettlement(false);
+
+    mint = await createPaymentToken();
+    await fund(mint, buyer, AMOUNT);
+    partnerOwnerAta = await fund(mint, partnerOwner, 0);
+    strangerAta = await fund(mint, stranger, 0);
+
+    await initPartner(code, partnerOwner.publicKey);
+    await purchase(buyer, code, mint, 0, false);
+  });
+
+  it("rejects a claim by anyone but the partner owner", async () => {
+    await expectError(
+      claim(code, mint, stranger, strangerAta).rpc(),
+      "Unauthorized"
+    );
+  });
+
+  it("lets the partner owner claim the token commission", async () => {
+    await claim(code, mint, partnerOwner, partnerOwnerAta).rpc();
+
+    expect(await balance(partnerOwnerAta)).to.equal(COMMISSION);
+    expect(
+      await balance(getAssociatedTokenAddressSync(mint, partner, true))
+    ).to.equal(0);
+
+    const reward = await program.account.partnerTokenReward.fetch(
+      partnerRewardPda(partner, mint)
+    );
+    expect(reward.reward.toNumber()).to.equal(0);
+    expect(reward.claimed.toNumber()).to.equal(COMMISSION);
+  });
+});
@@ -0,0 +1,89 @@
+import { BN } from "@coral-xyz/anchor";
+import { Keypair, PublicKey } from "@solana/web3.js";
+import { expect } from "chai";
+import {
+  AMOUNT,
+  COMMISSION,
+  airdrop,
+  balance,
+  claim,
+  createPaymentToken,
+  expectError,
+  fund,
+  initPartner,
+  initSaleHandler,
+  initSignerSet,
+  partnerPda,
+  program,
+  purchase,
+  setSettlement,
+  SignedClaim,
+  signClaim,
+  signerSet,
+} from "./utils";
+
+describe("signed claim", () => {
+  const code = "signed01";
+  const buyer = Keypair.generate();
+  const partnerOwner = Keypair.generate();
+  const claimer = Keypair.generate();
+  const backend = Keypair.generate();
+  const partner = partnerPda(code);
+
+  let mint: PublicKey;
+  let claimerAta: PublicKey;
+  let signed: SignedClaim;
+
+  before(async () => {
+    await airdrop(buyer, claimer);
+    await initSaleHandler();
+    await setSettlement(false);
+    await initSignerSet();
+    await program.methods
+      .addSigner(backend.publicKey, new BN(0))
+      .accountsPartial({ signerSet })
+      .rpc();
+
+    mint = await createPaymentToken();
+    await fund(mint, buyer, 2 * AMOUNT);
+    claimerAta = await fund(mint, claimer, 0);
+
+    await initPartner(code, partnerOwner.publicKey);
+    await program.methods
+      .setPartnerSignedClaims(true)
+      .accountsPartial({ partner })
+      .rpc();
+    await purchase(buyer, code, mint, 0, false);
+  });
+
+  it("pays a backend signed claim and consumes the nonce", async () => {
+    signed = await signClaim(backend, mint, code, claimer.publicKey);
+
+    await claim(code, mint, claimer, claimerAta, signed)
+      .preInstructions([signed.ix])
+      .rpc();
+
+    expect(await balance(claimerAta)).to.equal(COMMISSION);
+    const account = await program.account.partner.fetch(partner);
+    expect(account.nonce.toNumber()).to.equal(1);
+  });
+
+  it("rejects a replayed signature", async () => {
+    await purchase(buyer, code, mint, 1, false);
+
+    // The signature covered nonce 0
+    await expectError(
+      claim(code, mint, claimer, claimerAta, signed)
+        .preInstructions([signed.ix])
+        .rpc(),
+      "SignatureVerificationFailedNoMatch"
+    );
+  });
+
+  it("rejects an unsigned claim by anyone but the owner", async () => {
+    await expectError(
+      claim(code, mint, claimer, claimerAta).rpc(),
+      "Unauthorized"
+    );
+  });
+});
@@ -0,0 +1,106 @@
+import { BN } from "@coral-xyz/anchor";
+import { Keypair, PublicKey } from "@solana/web3.js";
+import { expect } from "chai";
+import {
+  AMOUNT,
+  COMMISSION,
+  airdrop,
+  balance,
+  claim,
+  createPaymentToken,
+  expectError,
+  fund,
+  initPartner,
+  initSaleHandler,
+  initSignerSet,
+  partnerPda,
+  program,
+  purchase,
+  setSettlement,
+  signClaim,
+  signerSet,
+} from "./utils";
+
+describe("signer rotation", () => {
+  const code = "rotation01";
+  const buyer = Keypair.generate();
+  const partnerOwner = Keypair.generate();
+  const claimer = Keypair.generate();
+  const retiring = Keypair.generate();
+  const retired = Keypair.generate();
+  const rotatedIn = Keypair.generate();
+
+  let mint: PublicKey;
+  let claimerAta: PublicKey;
+
+  const addSigner = (signer: Keypair) =>
+    program.methods
+      .addSigner(signer.publicKey, new BN(0))
+      .accountsPartial({ signerSet })
+      .rpc();
+
+  const retireSigner = (signer: Keypair, gracePeriod: number) =>
+    program.methods
+      .retireSigner(signer.publicKey, new BN(gracePeriod))
+      .accountsPartial({ signerSet })
+      .rpc();
+
+  const signedClaim = async (signer: Keypair) => {
+    const signed = await signClaim(signer, mint, code, claimer.publicKey);
+
+    return claim(code, mint, claimer, claimerAta, signed)
+      .preInstructions([signed.ix])
+      .rpc();
+  };
+
+  before(async () => {
+    await airdrop(buyer, claimer);
+    await initSaleHandler();
+    await setSettlement(false);
+    await initSignerSet();
+
+    mint = await createPaymentToken();
+    await fund(mint, buyer, 3 * AMOUNT);
+    claimerAta = await fund(mint, claimer, 0);
+
+    await initPartner(code, partnerOwner.publicKey);
+    await program.methods
+      .setPartnerSignedClaims(true)
+      .accountsPartial({ partner: partnerPda(code) })
+      .rpc();
+  });
+
+  it("rejects a negative grace period", async () => {
+    await addSigner(retiring);
+
+    await expectError(retireSigner(retiring, -1), "WrongGracePeriod");
+  });
+
+  it("accepts a retiring key during its grace period", async () => {
+    await retireSigner(retiring, 3600);
+    await purchase(buyer, code, mint, 0, false);
+
+    await signedClaim(retiring);
+
+    expect(await balance(claimerAta)).to.equal(COMMISSION);
+  });
+
+  it("rejects a key once its grace period is over", async () => {
+    await addSigner(retired);
+    await retireSigner(retired, 0);
+    await purchase(buyer, code, mint, 1, false);
+
+    await expectError(
+      signedClaim(retired),
+      "SignatureVerificationFailedNoMatch"
+    );
+  });
+
+  it("accepts the key rotated in", async () => {
+    await addSigner(rotatedIn);
+
+    await signedClaim(rotatedIn);
+
+    expect(await balance(claimerAta)).to.equal(2 * COMMISSION);
+  });
+});
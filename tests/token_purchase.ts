@@ -0,0 +1,70 @@
+import { BN } from "@coral-xyz/anchor";
+import { Keypair, PublicKey } from "@solana/web3.js";
+import { getAssociatedTokenAddressSync } from "@solana/spl-token";
+import { expect } from "chai";
+import {
+  AMOUNT,
+  BANK,
+  COMMISSION,
+  PRECISION,
+  airdrop,
+  balance,
+  createPaymentToken,
+  fund,
+  initPartner,
+  initSaleHandler,
+  partnerPda,
+  partnerRewardPda,
+  program,
+  purchase,
+  purchaserPda,
+  setSettlement,
+} from "./utils";
+
+describe("token purchase", () => {
+  const code = "purchase01";
+  const buyer = Keypair.generate();
+  const partnerOwner = Keypair.generate();
+  const partner = partnerPda(code);
+
+  let mint: PublicKey;
+
+  before(async () => {
+    await airdrop(buyer);
+    await initSaleHandler();
+    await setSettlement(false);
+
+    mint = await createPaymentToken();
+    await fund(mint, buyer, AMOUNT);
+    await initPartner(code, partnerOwner.publicKey);
+  });
+
+  it("pays the partner commission on a token purchase", async () => {
+    await purchase(buyer, code, mint, 0, false);
+
+    expect(
+      await balance(getAssociatedTokenAddressSync(mint, partner, true))
+    ).to.equal(COMMISSION);
+    expect(await balance(getAssociatedTokenAddressSync(mint, BANK))).to.equal(
+      AMOUNT - COMMISSION
+    );
+
+    const reward = await program.account.partnerTokenReward.fetch(
+      partnerRewardPda(partner, mint)
+    );
+    expect(reward.reward.toNumber()).to.equal(COMMISSION);
+    // $10 purchase
+    expect(reward.volumeUsd.toString()).to.equal(
+      new BN(10).mul(new BN(PRECISION)).toString()
+    );
+
+    // $10 at $0.1 per token
+    const account = await program.account.purchaser.fetch(
+      purchaserPda(buyer.publicKey)
+    );
+    expect(account.purchased.toString()).to.equal(
+      new BN(100).mul(new BN(PRECISION)).toString()
+    );
+    expect(account.partnerCode).to.equal(code);
+  });
+});
@@ -0,0 +1,165 @@
+import { BN } from "@coral-xyz/anchor";
+import {
+  Keypair,
+  PublicKey,
+  SystemProgram,
+  Transaction,
+} from "@solana/web3.js";
+import {
+  createInitializeMintInstruction,
+  createInitializeTransferFeeConfigInstruction,
+  ExtensionType,
+  getAssociatedTokenAddressSync,
+  getMintLen,
+  TOKEN_2022_PROGRAM_ID,
+} from "@solana/spl-token";
+import { expect } from "chai";
+import {
+  AMOUNT,
+  BANK,
+  admin,
+  airdrop,
+  balance,
+  escrow,
+  fund,
+  initAcceptedToken,
+  initEscrow,
+  initPartner,
+  initSaleHandler,
+  partnerPda,
+  partnerRewardPda,
+  program,
+  provider,
+  purchase,
+  purchaseRecordPda,
+  setSettlement,
+  settle,
+} from "./utils";
+
+// 1% fee withheld on every transfer
+const FEE_BASIS_POINTS = 100;
+const fee = (amount: number) => Math.ceil((amount * FEE_BASIS_POINTS) / 10_000);
+
+describe("transfer fee", () => {
+  const code = "fee01";
+  const buyer = Keypair.generate();
+  const partnerOwner = Keypair.generate();
+  const mintKeypair = Keypair.generate();
+  const mint = mintKeypair.publicKey;
+  const partner = partnerPda(code);
+  const partnerReward = partnerRewardPda(partner, mint);
+  const ata = (owner: PublicKey) =>
+    getAssociatedTokenAddressSync(mint, owner, true, TOKEN_2022_PROGRAM_ID);
+
+  // Default sale handler main interest, 15%, less the purchase transfer fee
+  const commission = 1_500_000 - fee(1_500_000);
+  // What reaches the partner once settlement moves it out of escrow
+  const settled = commission - fee(commission);
+
+  before(async () => {
+    await airdrop(buyer);
+    await initSaleHandler();
+    await initEscrow();
+    await setSettlement(true);
+
+    const space = getMintLen([ExtensionType.TransferFeeConfig]);
+    await provider.sendAndConfirm(
+      new Transaction().add(
+        SystemProgram.createAccount({
+          fromPubkey: admin.publicKey,
+          newAccountPubkey: mint,
+          space,
+          lamports:
+            await provider.connection.getMinimumBalanceForRentExemption(space),
+          programId: TOKEN_2022_PROGRAM_ID,
+        }),
+        createInitializeTransferFeeConfigInstruction(
+          mint,
+          admin.publicKey,
+          admin.publicKey,
+          FEE_BASIS_POINTS,
+          BigInt(AMOUNT),
+          TOKEN_2022_PROGRAM_ID
+        ),
+        createInitializeMintInstruction(
+          mint,
+          6,
+          admin.publicKey,
+          null,
+          TOKEN_2022_PROGRAM_ID
+        )
+      ),
+      [mintKeypair]
+    );
+
+    await initAcceptedToken(mint);
+    await fund(mint, buyer, AMOUNT, TOKEN_2022_PROGRAM_ID);
+    await initPartner(code, partnerOwner.publicKey);
+  });
+
+  after(() => setSettlement(false));
+
+  it("credits the commission net of the purchase transfer fee", async () => {
+    await purchase(buyer, code, mint, 0, true, TOKEN_2022_PROGRAM_ID);
+
+    expect(await balance(ata(escrow), TOKEN_2022_PROGRAM_ID)).to.equal(
+      commission
+    );
+    const reward = await program.account.partnerTokenReward.fetch(
+      partnerReward
+    );
+    expect(reward.deferredReward.toNumber()).to.equal(commission);
+
+    const record = await program.account.purchaseRecord.fetch(
+      purchaseRecordPda(buyer.publicKey, 0)
+    );
+    expect(record.rewards.map((reward) => reward.toNumber())).to.deep.equal([
+      commission,
+    ]);
+    expect(record.deferred).to.equal(true);
+  });
+
+  it("credits the settled commission net of the settlement transfer fee", async () => {
+    await settle(code, mint, TOKEN_2022_PROGRAM_ID);
+
+    expect(await balance(ata(partner), TOKEN_2022_PROGRAM_ID)).to.equal(
+      settled
+    );
+    const reward = await program.account.partnerTokenReward.fetch(
+      partnerReward
+    );
+    expect(reward.deferredReward.toNumber()).to.equal(0);
+    expect(reward.reward.toNumber()).to.equal(settled);
+  });
+
+  it("claws back what reached the partner without booking debt", async () => {
+    await program.methods
+      .clawbackTokenPurchase(buyer.publicKey, new BN(0), [code])
+      .accountsPartial({
+        purchaseRecord: purchaseRecordPda(buyer.publicKey, 0),
+        escrow,
+        mint,
+        escrowAta: ata(escrow),
+        bankAta: ata(BANK),
+        tokenProgram: TOKEN_2022_PROGRAM_ID,
+      })
+      .remainingAccounts([
+        { pubkey: partner, isWritable: true, isSigner: false },
+        { pubkey: partnerReward, isWritable: true, isSigner: false },
+        { pubkey: ata(partner), isWritable: true, isSigner: false },
+      ])
+      .rpc();
+
+    expect(await balance(ata(partner), TOKEN_2022_PROGRAM_ID)).to.equal(0);
+    const reward = await program.account.partnerTokenReward.fetch(
+      partnerReward
+    );
+    expect(reward.reward.toNumber()).to.equal(0);
+    expect(reward.debt.toNumber()).to.equal(0);
+
+    const record = await program.account.purchaseRecord.fetch(
+      purchaseRecordPda(buyer.publicKey, 0)
+    );
+    expect(record.clawedBack).to.equal(true);
+  });
+});
@@ -0,0 +1,371 @@
+import * as anchor from "@coral-xyz/anchor";
+import { BN, Program } from "@coral-xyz/anchor";
+import {
+  Ed25519Program,
+  Keypair,
+  LAMPORTS_PER_SOL,
+  PublicKey,
+  SYSVAR_INSTRUCTIONS_PUBKEY,
+  TransactionInstruction,
+} from "@solana/web3.js";
+import {
+  createAssociatedTokenAccount,
+  createMint,
+  getAccount,
+  getAssociatedTokenAddressSync,
+  mintTo,
+  TOKEN_PROGRAM_ID,
+} from "@solana/spl-token";
+import * as ed from "@noble/ed25519";
+import { expect } from "chai";
+import { SaleHandler } from "../target/types/sale_handler";
+
+// Admin instructions check `config::OWNERS`,
+// run with one of the owner keypairs as the provider wallet
+export const BANK = new PublicKey(
+  "5rtu57yuSYYrqRe6VXJUAkZKU9RQpBiReuQ3CFKU2aCN"
+);
+export const PRECISION = 1_000_000_000;
+export const STEP_ID = 0;
+// 10 tokens of a 6 decimals mint pegged at $1
+export const AMOUNT = 10_000_000;
+// Default sale handler main interest, 15%
+export const COMMISSION = 1_500_000;
+
+export const provider = anchor.AnchorProvider.env();
+anchor.setProvider(provider);
+
+export const program = anchor.workspace.SaleHandler as Program<SaleHandler>;
+export const admin = provider.wallet as anchor.Wallet;
+
+export const tag = (name: string) => Buffer.from(name);
+export const u64 = (value: number) =>
+  new BN(value).toArrayLike(Buffer, "le", 8);
+export const pda = (...seeds: (Buffer | Uint8Array)[]) =>
+  PublicKey.findProgramAddressSync(seeds, program.programId)[0];
+
+export const saleHandler = pda();
+export const step = pda(
+  tag("STEP"),
+  tag("_"),
+  new BN(STEP_ID).toArrayLike(Buffer, "le", 2)
+);
+export const escrow = pda(tag("ESCROW"));
+export const signerSet = pda(tag("SIGNER_SET"));
+
+export const partnerPda = (code: string) =>
+  pda(tag("PARTNER"), tag("_"), tag(code));
+export const purchaserPda = (wallet: PublicKey) =>
+  pda(tag("PURCHASER"), tag("_"), wallet.toBuffer());
+export const purchaseRecordPda = (wallet: PublicKey, index: number) =>
+  pda(tag("PURCHASE_RECORD"), tag("_"), wallet.toBuffer(), u64(index));
+export const partnerRewardPda = (partner: PublicKey, mint: PublicKey) =>
+  pda(tag("PARTNER_REWARD"), tag("_"), partner.toBuffer(), mint.toBuffer());
+
+export const balance = async (
+  address: PublicKey,
+  tokenProgram: PublicKey = TOKEN_PROGRAM_ID
+) =>
+  Number(
+    (await getAccount(provider.connection, address, undefined, tokenProgram))
+      .amount
+  );
+
+export const expectError = async (promise: Promise<unknown>, code: string) => {
+  try {
+    await promise;
+  } catch (err) {
+    expect((err as anchor.AnchorError).error.errorCode.code).to.equal(code);
+    return;
+  }
+
+  expect.fail(`expected ${code}`);
+};
+
+export const airdrop = async (...wallets: Keypair[]) => {
+  for (const wallet of wallets) {
+    const signature = await provider.connection.requestAirdrop(
+      wallet.publicKey,
+      10 * LAMPORTS_PER_SOL
+    );
+    await provider.connection.confirmTransaction(signature);
+  }
+};
+
+// The sale handler and its first step are shared by every test file, created once
+export const initSaleHandler = async () => {
+  if (await program.account.saleHandler.fetchNullable(saleHandler)) {
+    return;
+  }
+
+  await program.methods.init().accountsPartial({ saleHandler }).rpc();
+  await program.methods
+    .initStep(
+      STEP_ID,
+      new BN(PRECISION / 10),
+      new BN(1_000_000).mul(new BN(PRECISION))
+    )
+    .accountsPartial({ step })
+    .rpc();
+  await program.methods.enableStep().accountsPartial({ step, saleHandler }).rpc();
+  await program.methods.enableSaleHandler().accountsPartial({ saleHandler }).rpc();
+};
+
+export const initEscrow = async () => {
+  if (await program.account.escrow.fetchNullable(escrow)) {
+    return;
+  }
+
+  await program.methods.initEscrow().accountsPartial({ escrow }).rpc();
+};
+
+export const initSignerSet = async () => {
+  if (await program.account.signerSet.fetchNullable(signerSet)) {
+    return;
+  }
+
+  await program.methods.initSignerSet().accountsPartial({ signerSet }).rpc();
+};
+
+export const setSettlement = (deferred: boolean) =>
+  program.methods
+    .setSaleHandlerSettlement(
+      deferred ? { deferred: {} } : { immediate: {} },
+      new BN(0)
+    )
+    .accountsPartial({ saleHandler })
+    .rpc();
+
+// Accepted token pegged at $1
+export const initAcceptedToken = async (mint: PublicKey) => {
+  const acceptedToken = pda(tag("ACCEPTED_TOKEN"), tag("_"), mint.toBuffer());
+
+  await program.methods
+    .initAcceptedToken(
+      PublicKey.default,
+      new Array(32).fill(0),
+      new BN(PRECISION),
+      new BN(1_000_000).mul(new BN(PRECISION)),
+      new BN(0)
+    )
+    .accountsPartial({ acceptedToken, mint })
+    .rpc();
+  await program.methods
+    .enableAcceptedToken()
+    .accountsPartial({ acceptedToken })
+    .rpc();
+};
+
+// 6 decimals SPL payment token accepted by the sale
+export const createPaymentToken = async () => {
+  const mint = await createMint(
+    provider.connection,
+    admin.payer,
+    admin.publicKey,
+    null,
+    6
+  );
+  await initAcceptedToken(mint);
+
+  return mint;
+};
+
+// Token account of `wallet` holding `amount` of `mint`
+export const fund = async (
+  mint: PublicKey,
+  wallet: Keypair,
+  amount: number,
+  tokenProgram: PublicKey = TOKEN_PROGRAM_ID
+) => {
+  const ata = await createAssociatedTokenAccount(
+    provider.connection,
+    wallet,
+    mint,
+    wallet.publicKey,
+    undefined,
+    tokenProgram
+  );
+
+  if (amount > 0) {
+    await mintTo(
+      provider.connection,
+      admin.payer,
+      mint,
+      ata,
+      admin.publicKey,
+      amount,
+      [],
+      undefined,
+      tokenProgram
+    );
+  }
+
+  return ata;
+};
+
+export const initPartner = (code: string, owner: PublicKey) =>
+  program.methods
+    .initPartner(code, owner, new BN(0), new BN(0))
+    .accountsPartial({ partner: partnerPda(code) })
+    .rpc();
+
+export const purchase = (
+  buyer: Keypair,
+  code: string,
+  mint: PublicKey,
+  index: number,
+  deferred: boolean,
+  tokenProgram: PublicKey = TOKEN_PROGRAM_ID
+) => {
+  const partner = partnerPda(code);
+
+  return program.methods
+    .purchaseWithToken(code, new BN(AMOUNT), "")
+    .accountsPartial({
+      saleHandler,
+      payer: buyer.publicKey,
+      step,
+      purchaser: purchaserPda(buyer.publicKey),
+      partner,
+      purchaseRecord: purchaseRecordPda(buyer.publicKey, index),
+      partnerReward: partnerRewardPda(partner, mint),
+      promo: null,
+      promoRedemption: null,
+      escrow: deferred ? escrow : null,
+      acceptedToken: pda(tag("ACCEPTED_TOKEN"), tag("_"), mint.toBuffer()),
+      mint,
+      priceUpdate: null,
+      purchaserAta: getAssociatedTokenAddressSync(
+        mint,
+        buyer.publicKey,
+        false,
+        tokenProgram
+      ),
+      bankInfo: BANK,
+      bankAta: getAssociatedTokenAddressSync(mint, BANK, false, tokenProgram),
+      partnerPdaAta: getAssociatedTokenAddressSync(
+        mint,
+        partner,
+        true,
+        tokenProgram
+      ),
+      escrowAta: deferred
+        ? getAssociatedTokenAddressSync(mint, escrow, true, tokenProgram)
+        : null,
+      tokenProgram,
+    })
+    .signers([buyer])
+    .rpc();
+};
+
+export const settle = (
+  code: string,
+  mint: PublicKey,
+  tokenProgram: PublicKey = TOKEN_PROGRAM_ID
+) => {
+  const partner = partnerPda(code);
+
+  return program.methods
+    .settlePartnerToken(code)
+    .accountsPartial({
+      partner,
+      partnerReward: partnerRewardPda(partner, mint),
+      saleHandler,
+      escrow,
+      mint,
+      escrowAta: getAssociatedTokenAddressSync(mint, escrow, true, tokenProgram),
+      partnerPdaAta: getAssociatedTokenAddressSync(
+        mint,
+        partner,
+        true,
+        tokenProgram
+      ),
+      tokenProgram,
+    })
+    .rpc();
+};
+
+// Claim of the `code` commission in `mint` by `payer` into its token account,
+// `signature` is required from anyone but the partner owner
+export const claim = (
+  code: string,
+  mint: PublicKey,
+  payer: Keypair,
+  payerAta: PublicKey,
+  signature?: { sig: number[]; deadline: BN; idx?: number }
+) => {
+  const partner = partnerPda(code);
+
+  return program.methods
+    .receivePaymentToken(
+      code,
+      new BN(COMMISSION),
+      signature ? signature.deadline : new BN(0),
+      signature ? signature.sig : null,
+      signature?.idx ?? 0
+    )
+    .accountsPartial({
+      partner,
+      partnerReward: partnerRewardPda(partner, mint),
+      saleHandler,
+      mint,
+      partnerAta: payerAta,
+      partnerPdaAta: getAssociatedTokenAddressSync(mint, partner, true),
+      tokenProgram: TOKEN_PROGRAM_ID,
+      signerSet: signature ? signerSet : null,
+      ixSysvar: SYSVAR_INSTRUCTIONS_PUBKEY,
+      payer: payer.publicKey,
+    })
+    .signers([payer]);
+};
+
+// Mirrors `signature::claim_message`
+export const claimMessage = (
+  mint: PublicKey,
+  code: string,
+  payer: PublicKey,
+  nonce: BN,
+  deadline: BN
+) =>
+  [
+    "SOLANEX_CLAIM_V1",
+    program.programId.toBase58(),
+    "receive",
+    mint.toBase58(),
+    code,
+    payer.toBase58(),
+    new BN(COMMISSION).toString(),
+    nonce.toString(),
+    deadline.toString(),
+  ].join("|");
+
+// Backend signature of a claim and the Ed25519 instruction carrying it
+export type SignedClaim = {
+  sig: number[];
+  deadline: BN;
+  ix: TransactionInstruction;
+};
+
+export const signClaim = async (
+  signer: Keypair,
+  mint: PublicKey,
+  code: string,
+  payer: PublicKey
+): Promise<SignedClaim> => {
+  const partner = await program.account.partner.fetch(partnerPda(code));
+  const deadline = new BN(Math.floor(Date.now() / 1000) + 3600);
+  const message = Buffer.from(
+    claimMessage(mint, code, payer, partner.nonce, deadline)
+  );
+  const signature = await ed.sign(message, signer.secretKey.slice(0, 32));
+
+  return {
+    sig: Array.from(signature),
+    deadline,
+    ix: Ed25519Program.createInstructionWithPublicKey({
+      publicKey: signer.publicKey.toBytes(),
+      message,
+      signature,
+    }),
+  };
+};